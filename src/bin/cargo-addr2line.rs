@@ -0,0 +1,9 @@
+const EXAMPLES: &str = "
+
+EXAMPLES
+
+`cargo addr2line --bin foo -- -f 0x0800123c` - prints the function and source location of an address";
+
+fn main() {
+    cargo_binutils::Tool::Addr2line.cargo_exec(Some(EXAMPLES))
+}
@@ -0,0 +1,9 @@
+const EXAMPLES: &str = "
+
+EXAMPLES
+
+`cargo cxxfilt < app.map > app.demangled.map` - demangles all the symbols in a linker map file";
+
+fn main() {
+    cargo_binutils::Tool::Cxxfilt.cargo_exec(Some(EXAMPLES))
+}
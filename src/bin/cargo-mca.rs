@@ -0,0 +1,10 @@
+const EXAMPLES: &str = "
+
+EXAMPLES
+
+`cargo mca -- -mcpu=cortex-m4 < loop.s`                    - analyzes the assembly of loop.s
+`cargo mca -- -mcpu=znver3 target/release/deps/app-*.s`     - analyzes the output of `--emit asm`";
+
+fn main() {
+    cargo_binutils::Tool::Mca.cargo_exec(Some(EXAMPLES))
+}
@@ -0,0 +1,9 @@
+const EXAMPLES: &str = "
+
+EXAMPLES
+
+`cargo ranlib -- libfoo.a` - regenerates the symbol index of the archive";
+
+fn main() {
+    cargo_binutils::Tool::Ranlib.cargo_exec(Some(EXAMPLES))
+}
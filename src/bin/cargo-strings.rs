@@ -0,0 +1,10 @@
+const EXAMPLES: &str = "
+
+EXAMPLES
+
+`cargo strings --release --bin foo`          - lists the printable strings in the binary
+`cargo strings --release --bin foo -- -n 16` - only lists the strings of at least 16 characters";
+
+fn main() {
+    cargo_binutils::Tool::Strings.cargo_exec(Some(EXAMPLES))
+}
@@ -0,0 +1,10 @@
+const EXAMPLES: &str = "
+
+EXAMPLES
+
+`cargo symbolizer --bin foo -- 0x0800123c 0x08000400` - resolves the given addresses
+`cargo symbolizer --bin foo < addresses.txt`          - resolves the addresses read from stdin";
+
+fn main() {
+    cargo_binutils::Tool::Symbolizer.cargo_exec(Some(EXAMPLES))
+}
@@ -0,0 +1,3 @@
+fn main() {
+    cargo_binutils::Tool::Addr2line.rust_exec()
+}
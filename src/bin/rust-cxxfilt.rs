@@ -0,0 +1,3 @@
+fn main() {
+    cargo_binutils::Tool::Cxxfilt.rust_exec()
+}
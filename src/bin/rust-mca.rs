@@ -0,0 +1,3 @@
+fn main() {
+    cargo_binutils::Tool::Mca.rust_exec()
+}
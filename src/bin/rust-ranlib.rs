@@ -0,0 +1,3 @@
+fn main() {
+    cargo_binutils::Tool::Ranlib.rust_exec()
+}
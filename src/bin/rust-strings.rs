@@ -0,0 +1,3 @@
+fn main() {
+    cargo_binutils::Tool::Strings.rust_exec()
+}
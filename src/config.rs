@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+// The parts of the Cargo config (`.cargo/config.toml` and `--config`) we care about
+
+#[derive(Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub build: Build,
+    /// The `[target.<triple>]` and `[target.'cfg(..)']` tables
+    #[serde(default)]
+    pub target: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Default, Deserialize)]
+pub struct Build {
+    pub target: Option<BuildTarget>,
+    pub rustflags: Option<Rustflags>,
+}
+
+/// `rustflags`, either a space separated string or a list of flags
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Rustflags {
+    String(String),
+    List(Vec<String>),
+}
+
+impl Rustflags {
+    pub fn flags(&self) -> Vec<String> {
+        match self {
+            Rustflags::String(flags) => flags.split_whitespace().map(str::to_owned).collect(),
+            Rustflags::List(flags) => flags.clone(),
+        }
+    }
+}
+
+/// `build.target`, which can be a list of targets since Cargo 1.64
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum BuildTarget {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl BuildTarget {
+    /// The configured target, erroring if there are several as we wouldn't know which one's
+    /// artifact to inspect
+    pub fn single(&self) -> Result<Option<&str>> {
+        match self {
+            BuildTarget::One(target) => Ok(Some(target)),
+            BuildTarget::Many(targets) => match &targets[..] {
+                [] => Ok(None),
+                [target] => Ok(Some(target)),
+                _ => bail!(
+                    "Several build targets are configured ({}), select one with `--target`",
+                    targets.join(", ")
+                ),
+            },
+        }
+    }
+}
+
+impl Config {
+    /// The configured `build.target`
+    pub fn build_target(&self) -> Result<Option<&str>> {
+        match &self.build.target {
+            Some(target) => target.single(),
+            None => Ok(None),
+        }
+    }
+
+    /// The `rustflags` of the `[target.<target>]` table, or else `build.rustflags`. Like Cargo's
+    /// they replace rather than add to each other, but unlike Cargo's the `[target.'cfg(..)']`
+    /// tables are ignored.
+    pub fn rustflags(&self, target: &str) -> Vec<String> {
+        let target_flags = self
+            .target
+            .get(target)
+            .and_then(|table| table.get("rustflags"))
+            .and_then(|flags| Rustflags::deserialize(flags.clone()).ok());
+
+        match target_flags.as_ref().or(self.build.rustflags.as_ref()) {
+            Some(flags) => flags.flags(),
+            None => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn build_targets() {
+        assert_eq!(config("").build_target().unwrap(), None);
+        assert_eq!(
+            config("build.target = 'thumbv7em-none-eabihf'")
+                .build_target()
+                .unwrap(),
+            Some("thumbv7em-none-eabihf")
+        );
+        assert_eq!(config("build.target = []").build_target().unwrap(), None);
+        assert_eq!(
+            config("build.target = ['thumbv6m-none-eabi']")
+                .build_target()
+                .unwrap(),
+            Some("thumbv6m-none-eabi")
+        );
+
+        let err = config("build.target = ['thumbv6m-none-eabi', 'thumbv7m-none-eabi']")
+            .build_target()
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("(thumbv6m-none-eabi, thumbv7m-none-eabi)"));
+    }
+
+    #[test]
+    fn build_rustflags() {
+        const TARGET: &str = "thumbv7em-none-eabihf";
+
+        assert!(config("").rustflags(TARGET).is_empty());
+        assert_eq!(
+            config("build.rustflags = '-C  link-arg=-Tlink.x'").rustflags(TARGET),
+            ["-C", "link-arg=-Tlink.x"]
+        );
+        assert_eq!(
+            config("build.rustflags = ['-C', 'link-arg=-T link.x']").rustflags(TARGET),
+            ["-C", "link-arg=-T link.x"]
+        );
+    }
+
+    #[test]
+    fn target_rustflags() {
+        let config = config(
+            "[build]\nrustflags = ['-Cdebuginfo=2']\n\
+             [target.thumbv7em-none-eabihf]\nrustflags = '-Ctarget-cpu=cortex-m4'\n\
+             [target.'cfg(target_os = \"none\")']\nrustflags = ['-Copt-level=s']",
+        );
+
+        // The target's flags replace the `build` ones
+        assert_eq!(
+            config.rustflags("thumbv7em-none-eabihf"),
+            ["-Ctarget-cpu=cortex-m4"]
+        );
+        assert_eq!(config.rustflags("thumbv6m-none-eabi"), ["-Cdebuginfo=2"]);
+    }
+}
@@ -0,0 +1,134 @@
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+
+// Just enough of a parser for the Makefile style dep-info (`.d`) files that cargo writes next to
+// the artifacts to tell whether an artifact is older than its sources.
+
+/// Reads the dependencies listed in the dep-info file at `path`, relative paths are resolved
+/// against `base`
+pub fn read(path: &Path, base: &Path) -> Result<Vec<PathBuf>> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+    Ok(parse(&text).into_iter().map(|dep| base.join(dep)).collect())
+}
+
+// Parses `$target: $dep1 $dep2 ..` rules, spaces in paths are escaped as `\ `
+fn parse(text: &str) -> Vec<PathBuf> {
+    // Rules can continue on the next line with a trailing `\`
+    let text = text.replace("\\\r\n", " ").replace("\\\n", " ");
+
+    let mut deps = vec![];
+    for line in text.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+
+        // The target is followed by `: ` rather than just `:` to not stop at the drive letter of
+        // Windows paths
+        let rest = match line.find(": ") {
+            Some(i) => &line[i + 2..],
+            None => continue,
+        };
+
+        let mut path = String::new();
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&' ') => {
+                    chars.next();
+                    path.push(' ');
+                }
+                ' ' | '\t' => {
+                    if !path.is_empty() {
+                        deps.push(mem::take(&mut path).into());
+                    }
+                }
+                c => path.push(c),
+            }
+        }
+        if !path.is_empty() {
+            deps.push(path.into());
+        }
+    }
+    deps
+}
+
+/// Whether `artifact` exists and was modified after all of its `deps`
+pub fn is_fresh<'a>(artifact: &Path, deps: impl IntoIterator<Item = &'a Path>) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+
+    match modified(artifact) {
+        Ok(artifact) => newer_than_all(artifact, deps.into_iter().map(modified)),
+        Err(_) => false,
+    }
+}
+
+// Whether `artifact` was modified after all of the `deps`
+fn newer_than_all(
+    artifact: SystemTime,
+    deps: impl IntoIterator<Item = io::Result<SystemTime>>,
+) -> bool {
+    deps.into_iter().all(|dep| match dep {
+        Ok(modified) => modified <= artifact,
+        // The dependency was removed (or renamed) since the last build
+        Err(_) => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn parse_rules() {
+        let deps = parse(
+            "# comment\n\
+             /p/target/debug/app: src/main.rs src/a\\ b.rs \\\n  src/lib.rs\n\
+             /p/target/debug/app.d: C:\\p\\build.rs\n",
+        );
+
+        assert_eq!(
+            deps,
+            ["src/main.rs", "src/a b.rs", "src/lib.rs", "C:\\p\\build.rs"]
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn missing_depinfo() {
+        let dir = std::env::temp_dir();
+        assert!(read(&dir.join("cargo-binutils-no-such-file.d"), &dir).is_err());
+    }
+
+    #[test]
+    fn freshness() {
+        let built = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let older = built - Duration::from_secs(1);
+        let newer = built + Duration::from_secs(1);
+
+        // Fresh
+        assert!(newer_than_all(built, [Ok(older), Ok(built)]));
+        assert!(newer_than_all(built, []));
+        // Stale
+        assert!(!newer_than_all(built, [Ok(older), Ok(newer)]));
+        // A missing dependency
+        let missing = io::Error::new(io::ErrorKind::NotFound, "missing");
+        assert!(!newer_than_all(built, [Ok(older), Err(missing)]));
+    }
+
+    #[test]
+    fn missing_artifact() {
+        let dir = std::env::temp_dir();
+        assert!(!is_fresh(&dir.join("cargo-binutils-no-such-artifact"), []));
+    }
+}
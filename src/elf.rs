@@ -0,0 +1,547 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+// Just enough of an ELF parser to answer questions about the build artifacts (e.g. whether they
+// contain debug info) without shelling out to `readobj`.
+
+/// An ELF section header
+pub struct Section {
+    pub name: String,
+    /// `sh_type`
+    pub kind: u32,
+    /// `sh_flags`
+    pub flags: u64,
+    /// Virtual address
+    pub addr: u64,
+    pub size: u64,
+}
+
+/// The column of the Berkeley (`size -B`) output a section counts towards
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Berkeley {
+    Text,
+    Data,
+    Bss,
+}
+
+impl Section {
+    /// The column this section counts towards, `None` for the sections that aren't loaded, e.g.
+    /// the debug info. This classifies the sections like `llvm-size` does.
+    pub fn berkeley(&self) -> Option<Berkeley> {
+        let alloc = self.flags & SHF_ALLOC != 0;
+        let write = self.flags & SHF_WRITE != 0;
+        let exec = self.flags & SHF_EXECINSTR != 0;
+
+        if alloc && (exec || !write) {
+            Some(Berkeley::Text)
+        } else if alloc && write && self.kind != SHT_NOBITS {
+            Some(Berkeley::Data)
+        } else if alloc && self.kind == SHT_NOBITS {
+            Some(Berkeley::Bss)
+        } else {
+            None
+        }
+    }
+}
+
+/// An ELF program header
+pub struct Segment {
+    pub kind: u32,
+    /// Virtual address
+    pub vaddr: u64,
+    /// Physical (load) address
+    pub paddr: u64,
+    pub memsz: u64,
+}
+
+/// `p_type` of loadable segments
+pub const PT_LOAD: u32 = 1;
+
+impl Segment {
+    /// Maps the virtual address `vma` to the address it's loaded at, if it's part of this loadable
+    /// segment
+    pub fn lma(&self, vma: u64) -> Option<u64> {
+        if self.kind == PT_LOAD && vma >= self.vaddr && vma - self.vaddr < self.memsz.max(1) {
+            self.paddr.checked_add(vma - self.vaddr)
+        } else {
+            None
+        }
+    }
+}
+
+/// The fields of the ELF file header
+pub struct Header {
+    pub is64: bool,
+    pub big_endian: bool,
+    /// `e_ident[EI_OSABI]`
+    pub os_abi: u8,
+    /// `e_type`
+    pub kind: u16,
+    pub machine: u16,
+    pub entry: u64,
+    pub flags: u32,
+}
+
+impl Header {
+    /// The name of the `e_type`
+    pub fn kind_name(&self) -> Option<&'static str> {
+        Some(match self.kind {
+            0 => "NONE",
+            1 => "REL",
+            2 => "EXEC",
+            3 => "DYN",
+            4 => "CORE",
+            _ => return None,
+        })
+    }
+
+    /// The name of the `e_machine`, for the architectures Rust supports
+    pub fn machine_name(&self) -> Option<&'static str> {
+        Some(match self.machine {
+            2 => "SPARC",
+            3 => "386",
+            4 => "68K",
+            8 => "MIPS",
+            20 => "PPC",
+            21 => "PPC64",
+            22 => "S390",
+            40 => "ARM",
+            43 => "SPARCV9",
+            62 => "X86_64",
+            83 => "AVR",
+            94 => "XTENSA",
+            105 => "MSP430",
+            164 => "HEXAGON",
+            183 => "AARCH64",
+            190 => "CUDA",
+            243 => "RISCV",
+            247 => "BPF",
+            258 => "LOONGARCH",
+            _ => return None,
+        })
+    }
+}
+
+/// An entry of the `.symtab` symbol table
+pub struct Symbol {
+    pub name: String,
+    /// Index of the section the symbol is defined in, `None` for undefined, absolute and common
+    /// symbols
+    pub section: Option<usize>,
+}
+
+/// `sh_type` of the symbol table
+const SHT_SYMTAB: u32 = 2;
+/// `sh_type` of the sections that occupy no space in the file, like `.bss`
+const SHT_NOBITS: u32 = 8;
+
+// `sh_flags`
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+
+/// A parsed ELF file
+pub struct Elf {
+    pub header: Header,
+    pub sections: Vec<Section>,
+    pub segments: Vec<Segment>,
+    pub symbols: Vec<Symbol>,
+}
+
+impl Elf {
+    /// Reads and parses the ELF file at `path`
+    pub fn read(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&bytes).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let reader = Reader::new(bytes)?;
+
+        let header = Header {
+            is64: reader.is64,
+            big_endian: reader.big_endian,
+            os_abi: reader.array::<1>(7)?[0],
+            kind: reader.u16(0x10)?,
+            machine: reader.u16(0x12)?,
+            entry: if reader.is64 {
+                reader.u64(0x18)?
+            } else {
+                reader.u32(0x18)?.into()
+            },
+            flags: if reader.is64 {
+                reader.u32(0x30)?
+            } else {
+                reader.u32(0x24)?
+            },
+        };
+
+        // Offsets of the `e_sh*` fields of the file header
+        let (shoff, shentsize, shnum, shstrndx) = if reader.is64 {
+            (
+                reader.u64(0x28)?,
+                reader.u16(0x3a)?,
+                reader.u16(0x3c)?,
+                reader.u16(0x3e)?,
+            )
+        } else {
+            (
+                reader.u32(0x20)?.into(),
+                reader.u16(0x2e)?,
+                reader.u16(0x30)?,
+                reader.u16(0x32)?,
+            )
+        };
+
+        // Offsets of the `e_ph*` fields
+        let (phoff, phentsize, phnum) = if reader.is64 {
+            (reader.u64(0x20)?, reader.u16(0x36)?, reader.u16(0x38)?)
+        } else {
+            (
+                reader.u32(0x1c)?.into(),
+                reader.u16(0x2a)?,
+                reader.u16(0x2c)?,
+            )
+        };
+
+        let mut segments = vec![];
+        if phoff != 0 {
+            for i in 0..u64::from(phnum) {
+                segments.push(reader.program_header(entry(phoff, i, phentsize.into())?)?);
+            }
+        }
+
+        let mut headers = vec![];
+        if shoff != 0 {
+            if u64::from(shentsize) < if reader.is64 { 0x40 } else { 0x28 } {
+                bail!("invalid section header size {shentsize}");
+            }
+
+            let first = reader.section_header(shoff)?;
+            // Files with too many sections store the real count and string table index in the
+            // first section header
+            let shnum = if shnum == 0 { first.size } else { shnum.into() };
+            let shstrndx = if shstrndx == 0xffff {
+                first.link.into()
+            } else {
+                u64::from(shstrndx)
+            };
+
+            for i in 0..shnum {
+                headers.push(reader.section_header(entry(shoff, i, shentsize.into())?)?);
+            }
+
+            if let Some(strtab) = headers.get(shstrndx as usize) {
+                let strtab = reader.slice(strtab.offset, strtab.size)?;
+                for header in &mut headers {
+                    header.name = c_str(strtab, header.name_offset);
+                }
+            }
+        }
+
+        let mut symbols = vec![];
+        if let Some(symtab) = headers.iter().find(|header| header.kind == SHT_SYMTAB) {
+            let strtab = match headers.get(symtab.link as usize) {
+                Some(strtab) => reader.slice(strtab.offset, strtab.size)?,
+                None => &[],
+            };
+
+            let entsize = if symtab.entsize != 0 {
+                symtab.entsize
+            } else if reader.is64 {
+                24
+            } else {
+                16
+            };
+            for i in 0..symtab.size / entsize {
+                let symbol = reader.sub(entry(symtab.offset, i, entsize)?, entsize)?;
+                let (name, shndx) = if reader.is64 {
+                    (symbol.u32(0)?, symbol.u16(6)?)
+                } else {
+                    (symbol.u32(0)?, symbol.u16(14)?)
+                };
+
+                symbols.push(Symbol {
+                    name: c_str(strtab, name),
+                    // `SHN_UNDEF` and the reserved `SHN_LORESERVE..` indices
+                    section: Some(usize::from(shndx)).filter(|&i| i != 0 && i < 0xff00),
+                });
+            }
+        }
+
+        Ok(Elf {
+            header,
+            sections: headers
+                .into_iter()
+                .map(|header| Section {
+                    name: header.name,
+                    kind: header.kind,
+                    flags: header.flags,
+                    addr: header.addr,
+                    size: header.size,
+                })
+                .collect(),
+            segments,
+            symbols,
+        })
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    /// The symbols defined in the sections matching `matches`
+    pub fn symbols_in<'a>(
+        &'a self,
+        matches: impl Fn(&Section) -> bool + 'a,
+    ) -> impl Iterator<Item = &'a Symbol> + 'a {
+        self.symbols.iter().filter(move |symbol| {
+            symbol
+                .section
+                .and_then(|i| self.sections.get(i))
+                .is_some_and(&matches)
+        })
+    }
+}
+
+struct SectionHeader {
+    name_offset: u32,
+    name: String,
+    kind: u32,
+    flags: u64,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    entsize: u64,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    is64: bool,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self> {
+        if !bytes.starts_with(b"\x7fELF") {
+            bail!("not an ELF file");
+        }
+
+        let is64 = match bytes.get(4) {
+            Some(1) => false,
+            Some(2) => true,
+            _ => bail!("unknown ELF class"),
+        };
+        let big_endian = match bytes.get(5) {
+            Some(1) => false,
+            Some(2) => true,
+            _ => bail!("unknown ELF data encoding"),
+        };
+
+        Ok(Reader {
+            bytes,
+            is64,
+            big_endian,
+        })
+    }
+
+    // The `len` bytes at `offset`, which all the offsets of the fields are relative to
+    fn sub(&self, offset: u64, len: u64) -> Result<Reader<'a>> {
+        Ok(Reader {
+            bytes: self.slice(offset, len)?,
+            ..*self
+        })
+    }
+
+    fn slice(&self, offset: u64, len: u64) -> Result<&'a [u8]> {
+        usize::try_from(offset)
+            .ok()
+            .zip(usize::try_from(len).ok())
+            .and_then(|(offset, len)| self.bytes.get(offset..offset.checked_add(len)?))
+            .context("ELF file is truncated")
+    }
+
+    fn array<const N: usize>(&self, offset: u64) -> Result<[u8; N]> {
+        let mut array: [u8; N] = self.slice(offset, N as u64)?.try_into().unwrap();
+        if self.big_endian {
+            array.reverse();
+        }
+        Ok(array)
+    }
+
+    fn u16(&self, offset: u64) -> Result<u16> {
+        self.array(offset).map(u16::from_le_bytes)
+    }
+
+    fn u32(&self, offset: u64) -> Result<u32> {
+        self.array(offset).map(u32::from_le_bytes)
+    }
+
+    fn u64(&self, offset: u64) -> Result<u64> {
+        self.array(offset).map(u64::from_le_bytes)
+    }
+
+    fn program_header(&self, offset: u64) -> Result<Segment> {
+        let header = self.sub(offset, if self.is64 { 0x38 } else { 0x20 })?;
+        let kind = header.u32(0)?;
+        let (vaddr, paddr, memsz) = if self.is64 {
+            (header.u64(0x10)?, header.u64(0x18)?, header.u64(0x28)?)
+        } else {
+            (
+                header.u32(0x08)?.into(),
+                header.u32(0x0c)?.into(),
+                header.u32(0x14)?.into(),
+            )
+        };
+
+        Ok(Segment {
+            kind,
+            vaddr,
+            paddr,
+            memsz,
+        })
+    }
+
+    fn section_header(&self, offset: u64) -> Result<SectionHeader> {
+        let header = self.sub(offset, if self.is64 { 0x40 } else { 0x28 })?;
+        let (flags, addr, offset, size, link, entsize) = if self.is64 {
+            (
+                header.u64(0x08)?,
+                header.u64(0x10)?,
+                header.u64(0x18)?,
+                header.u64(0x20)?,
+                header.u32(0x28)?,
+                header.u64(0x38)?,
+            )
+        } else {
+            (
+                header.u32(0x08)?.into(),
+                header.u32(0x0c)?.into(),
+                header.u32(0x10)?.into(),
+                header.u32(0x14)?.into(),
+                header.u32(0x18)?,
+                header.u32(0x24)?.into(),
+            )
+        };
+
+        Ok(SectionHeader {
+            name_offset: header.u32(0)?,
+            name: String::new(),
+            kind: header.u32(4)?,
+            flags,
+            addr,
+            offset,
+            size,
+            link,
+            entsize,
+        })
+    }
+}
+
+// The offset of the `index`th entry of a table of `size` byte entries at `offset`
+fn entry(offset: u64, index: u64, size: u64) -> Result<u64> {
+    index
+        .checked_mul(size)
+        .and_then(|start| offset.checked_add(start))
+        .context("ELF file is truncated")
+}
+
+// Reads the NUL terminated string at `offset` of a string table
+fn c_str(strtab: &[u8], offset: u32) -> String {
+    let bytes = strtab.get(offset as usize..).unwrap_or_default();
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A little endian ELF64 file with a `.shstrtab` and a `.text` section
+    pub(crate) fn elf64() -> Vec<u8> {
+        let strtab = b"\0.shstrtab\0.text\0";
+        let shoff = 0x40 + strtab.len() as u64;
+
+        let mut bytes = vec![0; 0x40];
+        bytes[..4].copy_from_slice(b"\x7fELF");
+        bytes[4] = 2;
+        bytes[5] = 1;
+        bytes[0x10..0x12].copy_from_slice(&2u16.to_le_bytes());
+        bytes[0x12..0x14].copy_from_slice(&62u16.to_le_bytes());
+        bytes[0x28..0x30].copy_from_slice(&shoff.to_le_bytes());
+        bytes[0x3a..0x3c].copy_from_slice(&0x40u16.to_le_bytes());
+        bytes[0x3c..0x3e].copy_from_slice(&3u16.to_le_bytes());
+        bytes[0x3e..0x40].copy_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(strtab);
+
+        let section = |name: u32, kind: u32, flags: u64, offset: u64, size: u64| {
+            let mut header = [0; 0x40];
+            header[..4].copy_from_slice(&name.to_le_bytes());
+            header[4..8].copy_from_slice(&kind.to_le_bytes());
+            header[8..0x10].copy_from_slice(&flags.to_le_bytes());
+            header[0x18..0x20].copy_from_slice(&offset.to_le_bytes());
+            header[0x20..0x28].copy_from_slice(&size.to_le_bytes());
+            header
+        };
+        bytes.extend_from_slice(&[0; 0x40]);
+        bytes.extend_from_slice(&section(1, 3, 0, 0x40, strtab.len() as u64));
+        bytes.extend_from_slice(&section(11, 1, SHF_ALLOC | SHF_EXECINSTR, 0, 0x100));
+        bytes
+    }
+
+    #[test]
+    fn parse() {
+        let elf = Elf::parse(&elf64()).unwrap();
+
+        assert!(elf.header.is64);
+        assert_eq!(elf.header.machine_name(), Some("X86_64"));
+        assert_eq!(elf.header.kind_name(), Some("EXEC"));
+        let text = elf.section(".text").unwrap();
+        assert_eq!(text.size, 0x100);
+        assert!(text.berkeley() == Some(Berkeley::Text));
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = elf64();
+        for len in 0..bytes.len() {
+            assert!(Elf::parse(&bytes[..len]).is_err(), "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn out_of_range_offsets() {
+        let mut bytes = elf64();
+        // `e_shoff`
+        bytes[0x28..0x30].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+        assert!(Elf::parse(&bytes).is_err());
+
+        // The `sh_offset` of `.shstrtab`
+        let mut bytes = elf64();
+        let shoff = u64::from_le_bytes(bytes[0x28..0x30].try_into().unwrap()) as usize;
+        let strtab = shoff + 0x40;
+        bytes[strtab + 0x18..strtab + 0x20].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(Elf::parse(&bytes).is_err());
+
+        // `e_shentsize`
+        let mut bytes = elf64();
+        bytes[0x3a..0x3c].copy_from_slice(&0u16.to_le_bytes());
+        assert!(Elf::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn lma() {
+        let segment = Segment {
+            kind: PT_LOAD,
+            vaddr: 0x2000_0000,
+            paddr: u64::MAX - 4,
+            memsz: 0x100,
+        };
+        assert_eq!(segment.lma(0x2000_0004), Some(u64::MAX));
+        assert_eq!(segment.lma(0x2000_0008), None);
+        assert_eq!(segment.lma(0x1000), None);
+    }
+}
@@ -1,26 +1,50 @@
-use std::io::{self, BufReader, Write};
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
 use std::{env, str};
 
-use anyhow::{bail, Result};
-use cargo_metadata::camino::Utf8Component;
+use anyhow::{bail, Context as _, Result};
+use cargo_metadata::camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use cargo_metadata::diagnostic::DiagnosticLevel;
 use cargo_metadata::{Artifact, CargoOpt, Message, Metadata, MetadataCommand};
+use clap::error::{ContextKind, ContextValue, ErrorKind};
 use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
 use rustc_cfg::Cfg;
 
+use crate::config::Config;
+
 pub use tool::Tool;
 
+mod config;
+mod depinfo;
+mod elf;
 mod llvm;
+mod memory;
+pub mod paths;
 mod postprocess;
 mod rustc;
+mod symbols;
 mod tool;
+mod watch;
 
 /// Search for `file` in `path` and its parent directories
 fn search<'p>(path: &'p Path, file: &str) -> Option<&'p Path> {
     path.ancestors().find(|dir| dir.join(file).exists())
 }
 
+/// Search for the Cargo config, i.e. `.cargo/config.toml` or the deprecated `.cargo/config`, in
+/// `path` and its parent directories
+fn search_config(path: &Path) -> Option<PathBuf> {
+    path.ancestors().find_map(|dir| {
+        [".cargo/config.toml", ".cargo/config"]
+            .iter()
+            .map(|file| dir.join(file))
+            .find(|config| config.exists())
+    })
+}
+
 fn parse<T>(path: &Path) -> Result<T>
 where
     T: for<'de> serde::Deserialize<'de>,
@@ -34,18 +58,111 @@ where
     Ok(de::from_str(&s)?)
 }
 
+/// The file of the selected artifact that gets inspected
+struct ArtifactFile {
+    path: Utf8PathBuf,
+    /// Whether this is the executable of a bin, example or test
+    executable: bool,
+    /// The directory of the manifest of its package, unknown for `--file`
+    manifest_dir: Option<Utf8PathBuf>,
+}
+
+impl From<Artifact> for ArtifactFile {
+    fn from(artifact: Artifact) -> Self {
+        let manifest_dir = artifact.manifest_path.parent().map(|dir| dir.to_owned());
+        match &artifact.executable {
+            // Example and bins have an executable
+            Some(executable) => ArtifactFile {
+                path: executable.clone(),
+                executable: true,
+                manifest_dir,
+            },
+            None => ArtifactFile {
+                path: library_file(&artifact).clone(),
+                executable: false,
+                manifest_dir,
+            },
+        }
+    }
+}
+
+/// The `build.target` set with the `--config` flags, which are either `KEY=VALUE` pairs or paths of
+/// config files. As with cargo the last one wins.
+fn config_arg_target(matches: &ArgMatches) -> Result<Option<String>> {
+    let mut target = None;
+    for config in matches.get_many::<String>("config").into_iter().flatten() {
+        let path = Path::new(config);
+        let config: Config = if config.ends_with(".toml") && path.is_file() {
+            parse(path)?
+        } else {
+            match toml::from_str(config) {
+                Ok(config) => config,
+                // Let cargo report invalid values
+                Err(_) => continue,
+            }
+        };
+
+        if let Some(build_target) = config.build_target()? {
+            target = Some(build_target.to_owned());
+        }
+    }
+    Ok(target)
+}
+
+/// The target cargo builds for, from `--target`, `--config`, `CARGO_BUILD_TARGET` or the Cargo
+/// config in this order. `None` means the host.
+fn build_target(matches: &ArgMatches, metadata: &Metadata) -> Result<Option<String>> {
+    Ok(match matches.get_one::<String>("target") {
+        Some(target) => Some(target.clone()),
+        None => match config_arg_target(matches)? {
+            Some(target) => Some(target),
+            None => match env::var("CARGO_BUILD_TARGET") {
+                Ok(target) => Some(target),
+                Err(_) => config_file_target(metadata)?,
+            },
+        },
+    })
+}
+
+/// The `build.target` of the Cargo config of the workspace
+fn config_file_target(metadata: &Metadata) -> Result<Option<String>> {
+    let Some(path) = search_config(metadata.workspace_root.as_std_path()) else {
+        return Ok(None);
+    };
+
+    let config: Config = parse(&path)?;
+    Ok(config.build_target()?.map(|target| target.to_owned()))
+}
+
+/// Picks the file to inspect out of the `filenames` of a library artifact
+fn library_file(artifact: &Artifact) -> &Utf8PathBuf {
+    // Libs have an rlib and an rmeta, in no particular order. Other crate kinds (e.g. cdylib or
+    // staticlib) have no rlib so fall back to the first file that isn't metadata. The tools go
+    // through the members of archives like the `.a` of a staticlib by themselves.
+    let has_extension = |file: &&Utf8PathBuf, extension| file.extension() == Some(extension);
+    let files = &artifact.filenames;
+    files
+        .iter()
+        .find(|file| has_extension(file, "rlib"))
+        .or_else(|| files.iter().find(|file| !has_extension(file, "rmeta")))
+        .unwrap_or(&files[0])
+}
+
 /// Execution context
 // TODO this should be some sort of initialize once, read-only singleton
 pub struct Context {
-    cfg: Cfg,
-    /// Final compilation target
+    /// `cfg(target_arch)`
+    arch: String,
+    /// `cfg(target_endian)`
+    endian: String,
+    /// Final compilation target, the LLVM target of its spec for custom targets
     target: String,
 }
 
 impl Context {
     /* Constructors */
-    /// Get a context structure from a built artifact.
-    fn from_artifact(metadata: Metadata, artifact: &Artifact) -> Result<Self> {
+    /// Get a context structure from the path of a built artifact.
+    fn from_artifact(metadata: &Metadata, file: &Utf8Path) -> Result<Self> {
         // Currently there is no clean way to get the target triple from cargo so we can only make
         // an approximation, we do this by extracting the target triple from the artifacts path.
         // For more info on the path structure see: https://doc.rust-lang.org/cargo/guide/build-cache.html
@@ -53,12 +170,20 @@ impl Context {
         // In the future it may be possible to replace this code and use a cargo feature:
         // See: https://github.com/rust-lang/cargo/issues/5579, https://github.com/rust-lang/cargo/issues/8002
 
-        // Should always succeed.
-        let target_path = artifact.filenames[0].strip_prefix(metadata.target_directory)?;
+        let target_path = file
+            .strip_prefix(&metadata.target_directory)
+            .with_context(|| {
+                format!(
+                    "The artifact {file} is not in the target directory {}",
+                    metadata.target_directory
+                )
+            })?;
         let target_name = if let Some(Utf8Component::Normal(path)) = target_path.components().next()
         {
-            // TODO: How will custom profiles impact this?
-            if path == "debug" || path == "release" {
+            // The directories of the profiles, custom ones included, have a `.fingerprint`
+            // directory while those of the targets only contain the directories of the profiles
+            let profile_dir = metadata.target_directory.join(path).join(".fingerprint");
+            if path == "debug" || path == "release" || profile_dir.is_dir() {
                 // Looks like this artifact was built for the host.
                 rustc_version::version_meta()?.host
             } else {
@@ -74,46 +199,112 @@ impl Context {
 
     /// Get a context structure from a provided target flag, used when cargo
     /// was not used to build the binary.
-    fn from_flag(metadata: Metadata, target_flag: Option<&str>) -> Result<Self> {
+    fn from_flag(metadata: &Metadata, target_flag: Option<&str>) -> Result<Self> {
         let host_target_name = rustc_version::version_meta()?.host;
 
-        // Get the "default" target override in .cargo/config.
-        let mut config_target_name = None;
-        let config: toml::Value;
-
-        if let Some(path) = search(metadata.workspace_root.as_std_path(), ".cargo/config") {
-            config = parse(&path.join(".cargo/config"))?;
-            config_target_name = config
-                .get("build")
-                .and_then(|build| build.get("target"))
-                .and_then(|target| target.as_str());
-        }
+        // Get the "default" target override in .cargo/config.toml.
+        let config_path = search_config(metadata.workspace_root.as_std_path());
+        let config: Config = match &config_path {
+            Some(path) => parse(path)?,
+            None => Config::default(),
+        };
 
-        // Find the actual target.
-        let target_name = target_flag
-            .or(config_target_name)
-            .unwrap_or(&host_target_name);
+        // Find the actual target, like cargo the `CARGO_BUILD_TARGET` variable takes precedence
+        // over the config.
+        let env_target_name = env::var("CARGO_BUILD_TARGET").ok();
+        let target_name = match target_flag.or(env_target_name.as_deref()) {
+            Some(target_name) => Some(target_name),
+            None => config.build_target()?,
+        };
+        let target_name = match (target_name, lone_target(&config, &host_target_name)) {
+            (Some(target_name), _) => target_name,
+            (None, Some(target_name)) => {
+                eprintln!(
+                    "warning: no target specified, inferring `{target_name}` from the \
+                     `[target.{target_name}]` table in {}",
+                    config_path
+                        .as_deref()
+                        .unwrap_or(Path::new(".cargo/config.toml"))
+                        .display()
+                );
+                target_name
+            }
+            (None, None) => &host_target_name,
+        };
 
         Self::from_target_name(target_name)
     }
 
     fn from_target_name(target_name: &str) -> Result<Self> {
+        // rustc only prints the cfg of custom targets with `-Z unstable-options` so we read their
+        // spec instead. As their name says nothing about them (e.g. whether they are thumb
+        // targets) we use the LLVM target of the spec as the target.
+        if let Some(spec) = rustc::target_spec(target_name)? {
+            let spec = rustc::TargetSpec::read(&spec)?;
+
+            return Ok(Context {
+                arch: spec.arch,
+                endian: spec.endian,
+                target: spec.llvm_target,
+            });
+        }
+
         let cfg = Cfg::of(target_name)?;
 
         Ok(Context {
-            cfg,
+            arch: cfg.target_arch,
+            endian: cfg.target_endian,
             target: target_name.to_string(),
         })
     }
 }
 
+/// The flags cargo passes to rustc when building for `target`, from the environment or else the
+/// Cargo configuration
+fn rustflags(metadata: &Metadata, target: &str) -> Result<Vec<String>> {
+    if let Ok(flags) = env::var("CARGO_ENCODED_RUSTFLAGS") {
+        return Ok(flags
+            .split('\x1f')
+            .filter(|flag| !flag.is_empty())
+            .map(str::to_owned)
+            .collect());
+    }
+    if let Ok(flags) = env::var("RUSTFLAGS") {
+        return Ok(flags.split_whitespace().map(str::to_owned).collect());
+    }
+
+    let config: Config = match search_config(metadata.workspace_root.as_std_path()) {
+        Some(path) => parse(&path)?,
+        None => Config::default(),
+    };
+    Ok(config.rustflags(target))
+}
+
+/// Returns the triple of the only `[target.<triple>]` table in `config`, if that table is for a
+/// cross target. Embedded templates often configure a runner this way without setting
+/// `build.target`.
+fn lone_target<'a>(config: &'a Config, host_target_name: &str) -> Option<&'a str> {
+    // `[target.'cfg(..)']` tables don't name a target
+    let mut triples = config.target.keys().filter(|key| !key.starts_with("cfg("));
+    let triple = triples.next()?;
+
+    if triples.next().is_some() || triple == host_target_name {
+        None
+    } else {
+        Some(triple)
+    }
+}
+
 enum BuildType<'a> {
     Any,
+    /// Every target built with `--workspace` or `--all-targets`
+    All,
     Bin(&'a str),
     Example(&'a str),
     Test(&'a str),
     Bench(&'a str),
     Lib,
+    ProcMacro,
 }
 
 impl BuildType<'_> {
@@ -134,27 +325,72 @@ impl BuildType<'_> {
                 .kind
                 .iter()
                 .any(|s| s == "bin" || s == "example"),
-            // Since LibKind can be an arbitrary string `LibKind:Other(String)` we filter by what it can't be
+            // Since LibKind can be an arbitrary string `LibKind:Other(String)` we filter by what it can't be.
+            // Proc-macros are skipped as they are host dylibs, which is rarely what one wants to inspect.
             BuildType::Lib => artifact.target.kind.iter().any(|s| {
-                s != "bin" && s != "example" && s != "test" && s != "custom-build" && s != "bench"
+                s != "bin"
+                    && s != "example"
+                    && s != "test"
+                    && s != "custom-build"
+                    && s != "bench"
+                    && s != "proc-macro"
             }),
+            BuildType::ProcMacro => artifact.target.kind.iter().any(|s| s == "proc-macro"),
+            BuildType::All => artifact
+                .target
+                .kind
+                .iter()
+                .any(|s| s != "custom-build" && s != "proc-macro"),
         }
     }
 }
 
 fn args(tool: Tool, examples: Option<&str>) -> ArgMatches {
+    let name = tool.name();
+    command(tool, examples)
+        .try_get_matches()
+        .unwrap_or_else(|mut err| {
+            // A common mistake is to pass the flags of the tool before `--`, e.g. `cargo objdump -d`
+            if err.kind() == ErrorKind::UnknownArgument
+                && err.get(ContextKind::SuggestedArg).is_none()
+            {
+                if let Some(ContextValue::String(arg)) = err.get(ContextKind::InvalidArg) {
+                    let tip = format!(
+                        "did you mean `cargo {name} -- {arg}`? Flags for the tool go after `--`"
+                    );
+                    err.insert(
+                        ContextKind::Suggested,
+                        ContextValue::StyledStrs(vec![tip.into()]),
+                    );
+                }
+            }
+            err.exit()
+        })
+}
+
+/// The CLI of `cargo $tool`, whose help ends with `examples`
+pub fn command(tool: Tool, examples: Option<&str>) -> ClapCommand {
     let name = tool.name();
     let about = format!("Proxy for the `llvm-{name}` tool shipped with the Rust toolchain.");
+    let tool_args = tool_args(tool);
+    let tool_options = if tool_args.is_empty() {
+        ""
+    } else {
+        "\n\nThe \"Tool Options\" are shortcuts for commonly used flags of the proxied tool."
+    };
     let after_help = format!(
         "\
-The arguments specified *after* the `--` will be passed to the proxied tool invocation.
+The arguments specified *after* the `--` will be passed to the proxied tool invocation, after the
+ones in the `{}` environment variable.{}
 
 To see all the flags the proxied tool accepts run `cargo-{} -- --help`.{}",
+        env_flags_var(tool),
+        tool_options,
         name,
         examples.unwrap_or("")
     );
 
-    let app = ClapCommand::new(format!("cargo-{name}"))
+    let mut app = ClapCommand::new(format!("cargo-{name}"))
         .about(about)
         .version(env!("CARGO_PKG_VERSION"))
         // as this is used as a Cargo subcommand the first argument will be the name of the binary
@@ -166,6 +402,22 @@ To see all the flags the proxied tool accepts run `cargo-{} -- --help`.{}",
                 .short('v')
                 .action(ArgAction::Count)
                 .help("Use verbose output (-vv cargo verbose or -vvv for build.rs output)"),
+            Arg::new("no-demangle")
+                .long("no-demangle")
+                .action(ArgAction::SetTrue)
+                .help("Don't demangle the symbol names in the output of the tool"),
+            Arg::new("capture-stderr")
+                .long("capture-stderr")
+                .value_name("FILE")
+                .help("Write the stderr of the proxied tool to FILE instead of the terminal"),
+            Arg::new("output")
+                .long("output")
+                .value_name("PATH")
+                .help("Write the output of the tool to PATH instead of stdout"),
+            Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .help("Print the `rust-$tool` invocation instead of running it"),
             Arg::new("args")
                 .last(true)
                 .num_args(1..)
@@ -173,8 +425,8 @@ To see all the flags the proxied tool accepts run `cargo-{} -- --help`.{}",
         ])
         .after_help(after_help);
 
-    if tool.needs_build() {
-        app.args(&[
+    if tool.can_build() {
+        app = app.args(&[
             Arg::new("quiet")
                 .long("quiet")
                 .short('q')
@@ -195,6 +447,11 @@ To see all the flags the proxied tool accepts run `cargo-{} -- --help`.{}",
                 .action(ArgAction::SetTrue)
                 .conflicts_with_all(["bin", "example", "test", "bench"])
                 .help("Build only this package's library"),
+            Arg::new("proc-macro")
+                .long("proc-macro")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["lib", "bin", "example", "test", "bench"])
+                .help("Build only this package's library and inspect its proc-macro dylib"),
             Arg::new("bin")
                 .long("bin")
                 .value_name("NAME")
@@ -215,6 +472,48 @@ To see all the flags the proxied tool accepts run `cargo-{} -- --help`.{}",
                 .value_name("NAME")
                 .conflicts_with_all(["lib", "bin", "example", "test"])
                 .help("Build only the specified bench target"),
+            Arg::new("file")
+                .long("file")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(Utf8PathBuf))
+                .conflicts_with_all([
+                    "package",
+                    "lib",
+                    "proc-macro",
+                    "bin",
+                    "example",
+                    "test",
+                    "bench",
+                    "artifact-glob",
+                    "if-changed",
+                    "no-run-tool",
+                ])
+                .help("Inspect the file at PATH instead of building an artifact"),
+            Arg::new("artifact-glob")
+                .long("artifact-glob")
+                .value_name("PATTERN")
+                .help(
+                    "Only consider the artifacts with a file name matching PATTERN (e.g. `*.elf`)",
+                ),
+            Arg::new("if-changed")
+                .long("if-changed")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Skip `cargo build` if the artifact is newer than all of its sources and was \
+                     built with the same flags by the last `--if-changed`",
+                ),
+            Arg::new("no-run-tool")
+                .long("no-run-tool")
+                .action(ArgAction::SetTrue)
+                .help("Only build the artifact, quietly, without running the tool on it"),
+            Arg::new("print-artifact")
+                .long("print-artifact")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["file", "no-run-tool"])
+                .help(
+                    "Only build the artifact, quietly, and print its path instead of running the \
+                     tool",
+                ),
             Arg::new("release")
                 .long("release")
                 .action(ArgAction::SetTrue)
@@ -223,6 +522,10 @@ To see all the flags the proxied tool accepts run `cargo-{} -- --help`.{}",
                 .long("profile")
                 .value_name("PROFILE-NAME")
                 .help("Build artifacts with the specified profile"),
+            Arg::new("target-dir")
+                .long("target-dir")
+                .value_name("DIRECTORY")
+                .help("Directory for all generated artifacts"),
             Arg::new("manifest-path")
                 .long("manifest-path")
                 .help("Path to Cargo.tom"),
@@ -246,6 +549,7 @@ To see all the flags the proxied tool accepts run `cargo-{} -- --help`.{}",
             Arg::new("config")
                 .long("config")
                 .value_name("CONFIG")
+                .action(ArgAction::Append)
                 .help("Override a configuration value"),
             Arg::new("color")
                 .long("color")
@@ -271,14 +575,466 @@ To see all the flags the proxied tool accepts run `cargo-{} -- --help`.{}",
                 .action(ArgAction::Append)
                 .value_name("FLAG")
                 .help("Unstable (nightly-only) flags to Cargo, see 'cargo -Z help' for details"),
-        ])
-        .get_matches()
-    } else {
-        app.get_matches()
+        ]);
+    }
+
+    // Only these tools can inspect several artifacts at once
+    if matches!(tool, Tool::Nm | Tool::Size) {
+        app = app.args(&[
+            Arg::new("workspace")
+                .long("workspace")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["file", "if-changed"])
+                .help(
+                    "Build all the packages of the workspace and inspect each of their artifacts",
+                ),
+            Arg::new("all-targets")
+                .long("all-targets")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["file", "if-changed"])
+                .help("Build all the targets and inspect each of their artifacts"),
+        ]);
+    }
+
+    if !tool_args.is_empty() {
+        app = app.next_help_heading("Tool Options").args(tool_args);
     }
+
+    app
 }
 
-pub fn run(tool: Tool, matches: ArgMatches) -> Result<i32> {
+/// Lists the tools and whether they are installed, this is `cargo binutils`
+pub fn binutils_exec() -> ! {
+    ClapCommand::new("cargo-binutils")
+        .about("Lists the LLVM tools cargo-binutils provides subcommands for.")
+        .version(env!("CARGO_PKG_VERSION"))
+        // as this is used as a Cargo subcommand the first argument will be the name of the binary
+        // we ignore this argument
+        .arg(Arg::new("binary-name").hide(true))
+        .get_matches();
+
+    match list_tools() {
+        Err(e) => {
+            eprintln!("error: {e}");
+            process::exit(101)
+        }
+        Ok(ec) => process::exit(ec),
+    }
+}
+
+fn list_tools() -> Result<i32> {
+    let mut missing = false;
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{:<12} {:<10} DESCRIPTION", "TOOL", "STATUS")?;
+    for &tool in Tool::all() {
+        let installed = tool.path()?.exists();
+        missing |= !installed;
+
+        let status = if installed { "installed" } else { "missing" };
+        writeln!(
+            stdout,
+            "{:<12} {:<10} {}",
+            tool.name(),
+            status,
+            tool.description()
+        )?;
+    }
+
+    if missing {
+        writeln!(stdout, "\n{}", tool::missing_tool_hint())?;
+    }
+
+    Ok(0)
+}
+
+/// The convenience flags of `tool`, these are translated to flags of the proxied tool by `run`
+fn tool_args(tool: Tool) -> Vec<Arg> {
+    let mut args = match tool {
+        Tool::Objdump => vec![
+            Arg::new("source")
+                .long("source")
+                .action(ArgAction::SetTrue)
+                .help("Display source code intermixed with disassembly (`--source`)"),
+            Arg::new("source-dedup")
+                .long("source-dedup")
+                .action(ArgAction::SetTrue)
+                .help("Collapse the source lines the `--source` output repeats"),
+            Arg::new("arch-check")
+                .long("arch-check")
+                .action(ArgAction::SetTrue)
+                .help("Warn if the architecture passed to objdump doesn't match the ELF machine"),
+            Arg::new("function")
+                .long("function")
+                .value_name("SYMBOL")
+                .help("Only disassemble the given function, accepts demangled names"),
+            Arg::new("arch-name")
+                .long("arch-name")
+                .value_name("NAME")
+                .help(
+                    "Disassemble for the LLVM architecture NAME instead of the one of the target \
+                     (`--arch-name`), an empty NAME lets objdump detect it",
+                ),
+            Arg::new("mcpu")
+                .long("mcpu")
+                .value_name("CPU")
+                .help("Target a specific CPU type when disassembling (`--mcpu`)"),
+            Arg::new("no-target-cpu")
+                .long("no-target-cpu")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Don't pass the `target-cpu` and `target-feature` of the rustflags as `--mcpu` \
+                     and `--mattr`",
+                ),
+            Arg::new("no-aliases")
+                .long("no-aliases")
+                .action(ArgAction::SetTrue)
+                .help("Print canonical instruction names instead of aliases (`-M no-aliases`)"),
+            Arg::new("imm-hex")
+                .long("imm-hex")
+                .action(ArgAction::SetTrue)
+                .help("Print immediates in hexadecimal (`--print-imm-hex`)"),
+            Arg::new("disassemble-all")
+                .long("disassemble-all")
+                .action(ArgAction::SetTrue)
+                .help("Disassemble all sections, including data sections (`-D`)"),
+            Arg::new("wide")
+                .long("wide")
+                .action(ArgAction::SetTrue)
+                .help("Don't truncate long lines (`--wide`)"),
+        ],
+        Tool::Cov => vec![Arg::new("show")
+            .long("show")
+            .value_name("PATHS")
+            .num_args(1..)
+            .help(
+                "Build the selected executable and show its region coverage of the given source \
+                 files (`show --show-regions`)",
+            )],
+        Tool::Readobj => vec![
+            Arg::new("header-json")
+                .long("header-json")
+                .action(ArgAction::SetTrue)
+                .help("Print the fields of the ELF file header as JSON"),
+            Arg::new("symbol-versions")
+                .long("symbol-versions")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Display the GNU symbol versioning sections, `.gnu.version*` \
+                     (`--version-info`)",
+                ),
+            Arg::new("dump")
+                .long("dump")
+                .visible_alias("section-data")
+                .value_name("SECTION")
+                .action(ArgAction::Append)
+                .help(
+                    "Dump the contents of SECTION both as strings and in hex (`--string-dump` \
+                     and `--hex-dump`)",
+                ),
+        ],
+        Tool::Nm => vec![
+            Arg::new("dynamic")
+                .long("dynamic")
+                .action(ArgAction::SetTrue)
+                .help("Display the dynamic symbols instead of the normal symbols (`--dynamic`)"),
+            Arg::new("names-only")
+                .long("names-only")
+                .action(ArgAction::SetTrue)
+                .help("Only print the symbol names, one per line"),
+            Arg::new("line-numbers")
+                .long("line-numbers")
+                .action(ArgAction::SetTrue)
+                .help("Print the source location of each symbol using the debug info (`-l`)"),
+            Arg::new("section")
+                .long("section")
+                .value_name("NAME")
+                .help("Only list the symbols defined in the section NAME or its `NAME.*` sections"),
+            Arg::new("nm-format")
+                .long("nm-format")
+                .value_name("FORMAT")
+                .value_parser(clap::builder::PossibleValuesParser::new([
+                    "bsd",
+                    "darwin",
+                    "just-symbols",
+                    "posix",
+                    "sysv",
+                ]))
+                .help(
+                    "Select the output format (`--format`), `--names-only` and `--section` \
+                     understand all of them",
+                ),
+            Arg::new("sort")
+                .long("sort")
+                .value_name("KEY")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .value_parser(clap::builder::PossibleValuesParser::new([
+                    "address", "name", "size",
+                ]))
+                .help(
+                    "Sort the symbols by these comma separated keys, ties are broken by name and \
+                     address",
+                ),
+            Arg::new("reverse-sort")
+                .long("reverse-sort")
+                .action(ArgAction::SetTrue)
+                .requires("sort")
+                .help("Reverse the order of the first `--sort` key"),
+        ],
+        Tool::Objcopy => vec![
+            Arg::new("change-lma")
+                .long("change-lma")
+                .value_name("SECTION=DELTA")
+                .action(ArgAction::Append)
+                .value_parser(parse_change_lma)
+                .help(
+                    "Shift the load address (LMA) of a section by DELTA (`--change-section-lma`)",
+                ),
+            Arg::new("set-section-flags")
+                .long("set-section-flags")
+                .value_name("SECTION=FLAGS")
+                .action(ArgAction::Append)
+                .value_parser(parse_section_flags)
+                .help(
+                    "Set the comma-separated flags (e.g. `alloc,code`) of a section \
+                     (`--set-section-flags`)",
+                ),
+            Arg::new("entry").long("entry").value_name("SYMBOL").help(
+                "Set the entry point to the address of a symbol, accepts demangled names \
+                     (`--set-start`)",
+            ),
+            Arg::new("redefine-sym")
+                .long("redefine-sym")
+                .value_name("OLD=NEW")
+                .action(ArgAction::Append)
+                .value_parser(parse_redefine_sym)
+                .help(
+                    "Rename the symbol OLD to NEW, OLD can be a demangled name (`--redefine-sym`)",
+                ),
+            Arg::new("weaken-symbol")
+                .long("weaken-symbol")
+                .value_name("SYMBOL")
+                .action(ArgAction::Append)
+                .help("Make SYMBOL weak, it can be a demangled name (`--weaken-symbol`)"),
+            Arg::new("localize-symbol")
+                .long("localize-symbol")
+                .value_name("SYMBOL")
+                .action(ArgAction::Append)
+                .help("Make SYMBOL local, it can be a demangled name (`--localize-symbol`)"),
+            Arg::new("globalize-symbol")
+                .long("globalize-symbol")
+                .value_name("SYMBOL")
+                .action(ArgAction::Append)
+                .help("Make SYMBOL global, it can be a demangled name (`--globalize-symbol`)"),
+            Arg::new("split-debug")
+                .long("split-debug")
+                .value_name("DEBUG-FILE")
+                .num_args(0..=1)
+                .conflicts_with_all([
+                    "change-lma",
+                    "set-section-flags",
+                    "entry",
+                    "redefine-sym",
+                    "weaken-symbol",
+                    "localize-symbol",
+                    "globalize-symbol",
+                ])
+                .help(
+                    "Move the debug info of the artifact into DEBUG-FILE, `$artifact.debug` by \
+                     default, and link it back with `--add-gnu-debuglink`",
+                ),
+            Arg::new("compress-debug")
+                .long("compress-debug")
+                .value_name("ALGORITHM")
+                .num_args(0..=1)
+                .default_missing_value("zlib")
+                .value_parser(clap::builder::PossibleValuesParser::new(["zlib", "zstd"]))
+                .help(
+                    "Compress the debug sections, of DEBUG-FILE with `--split-debug`, using \
+                     ALGORITHM, zlib by default (`--compress-debug-sections`)",
+                ),
+        ],
+        Tool::Size => vec![
+            Arg::new("format")
+                .long("format")
+                .value_name("FORMAT")
+                .value_parser(clap::builder::PossibleValuesParser::new(["text", "json"]))
+                .help(
+                    "Print the sizes as text, the default, or as a JSON object with the Berkeley \
+                     totals and the sections",
+                ),
+            Arg::new("sort")
+                .long("sort")
+                .value_name("KEY")
+                .value_parser(clap::builder::PossibleValuesParser::new([
+                    "size", "name", "vma",
+                ]))
+                .help("Sort the sections of the System V (`-A`) output by the given key"),
+            Arg::new("lma")
+                .long("lma")
+                .action(ArgAction::SetTrue)
+                .help("Add the load address of the sections to the System V (`-A`) output"),
+            Arg::new("regions")
+                .long("regions")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Print how much of the memory regions of the `memory.x` linker script the \
+                     artifact uses",
+                ),
+            Arg::new("human")
+                .long("human")
+                .action(ArgAction::SetTrue)
+                .help("Add the sizes in KiB, MiB, etc. next to the byte counts"),
+            Arg::new("diff")
+                .long("diff")
+                .value_name("BASELINE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["regions", "totals-only", "format"])
+                .help("Print how the size of each section changed since the BASELINE artifact"),
+            Arg::new("diff-all")
+                .long("diff-all")
+                .action(ArgAction::SetTrue)
+                .requires("diff")
+                .help("Also print the sections whose size didn't change with `--diff`"),
+            Arg::new("totals-only")
+                .long("totals-only")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("regions")
+                .help("Only print the total size of the artifact"),
+            Arg::new("group-by-prefix")
+                .long("group-by-prefix")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Combine the sections of the System V (`-A`) output that share a prefix, e.g. \
+                     `.text.*` into `.text`",
+                ),
+        ],
+        Tool::Profdata => vec![Arg::new("merge-dir")
+            .long("merge-dir")
+            .value_name("DIR")
+            .value_parser(clap::value_parser!(PathBuf))
+            .help(
+                "Merge the `*.profraw` files in DIR into `default.profdata` \
+                 (`merge -sparse DIR/*.profraw -o default.profdata`)",
+            )],
+        Tool::Strip => vec![Arg::new("in-place")
+            .long("in-place")
+            .action(ArgAction::SetTrue)
+            .help("Strip the artifact itself (`-o $artifact`), which can't be an rlib")],
+        _ => vec![],
+    };
+
+    if tool == Tool::Size {
+        args.push(
+            Arg::new("watch")
+                .long("watch")
+                .action(ArgAction::SetTrue)
+                .help("Rebuild and print the sizes again whenever a file of the workspace changes"),
+        );
+    }
+
+    args
+}
+
+/// Parses a `SECTION=DELTA` pair, where `DELTA` is a signed decimal or hexadecimal number, into
+/// the `SECTION{+-}DELTA` form `llvm-objcopy` expects
+fn parse_change_lma(s: &str) -> Result<String, String> {
+    let (section, delta) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected `SECTION=DELTA`, found `{s}`"))?;
+
+    if section.is_empty() {
+        return Err("the section name can't be empty".to_owned());
+    }
+
+    let (sign, magnitude) = match delta.as_bytes().first() {
+        Some(b'-') => ("-", &delta[1..]),
+        Some(b'+') => ("+", &delta[1..]),
+        _ => ("+", delta),
+    };
+    let valid = match magnitude.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).is_ok(),
+        None => magnitude.parse::<u64>().is_ok(),
+    };
+    if !valid {
+        return Err(format!(
+            "`{delta}` is not a decimal or hexadecimal (0x) number"
+        ));
+    }
+
+    Ok(format!("{section}{sign}{magnitude}"))
+}
+
+/// Checks that `s` is an `OLD=NEW` pair of symbol names. As demangled names can contain `=`, e.g.
+/// `Fn<Output = T>`, the last `=` is used as the separator.
+fn parse_redefine_sym(s: &str) -> Result<String, String> {
+    match s.rsplit_once('=') {
+        Some((old, new)) if !old.is_empty() && !new.is_empty() => Ok(s.to_owned()),
+        _ => Err(format!("expected `OLD=NEW`, found `{s}`")),
+    }
+}
+
+/// The section flags `objcopy --set-section-flags` understands
+const SECTION_FLAGS: &[&str] = &[
+    "alloc", "code", "contents", "data", "debug", "exclude", "large", "load", "merge", "noload",
+    "readonly", "rom", "share", "strings",
+];
+
+/// Validates a `SECTION=FLAGS` value of `--set-section-flags`
+fn parse_section_flags(s: &str) -> Result<String, String> {
+    let (section, flags) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected `SECTION=FLAGS`, found `{s}`"))?;
+
+    if section.is_empty() {
+        return Err("the section name can't be empty".to_owned());
+    }
+
+    for flag in flags.split(',') {
+        if !SECTION_FLAGS.contains(&flag) {
+            return Err(format!(
+                "unknown section flag `{flag}`, expected one of: {}",
+                SECTION_FLAGS.join(", ")
+            ));
+        }
+    }
+
+    Ok(s.to_owned())
+}
+
+/// Warns if `file` is an ELF file of another architecture than the LLVM arch `arch_name`
+fn check_arch(arch_name: &str, file: &Path) {
+    // Other kinds of artifacts, e.g. rlibs, are not checked
+    let (Ok(elf), Some(expected)) = (elf::Elf::read(file), llvm::elf_machine(arch_name)) else {
+        return;
+    };
+
+    let machine = elf.header.machine;
+    if machine != expected {
+        eprintln!(
+            "warning: disassembling as `{arch_name}` but {} is an ELF file for {} (`e_machine` = \
+             {machine})",
+            file.display(),
+            elf.header.machine_name().unwrap_or("another architecture"),
+        );
+    }
+}
+
+/// Warns if `file` is an ELF without debug info, which some of the tool options rely on
+fn warn_if_no_debug_info(file: &Path) {
+    if let Ok(elf) = elf::Elf::read(file) {
+        if elf.section(".debug_info").is_none() {
+            eprintln!(
+                "warning: {} contains no debug info, build with `debug = true` in the Cargo \
+                 profile to map the output back to the source code",
+                file.display()
+            );
+        }
+    }
+}
+
+/// Reads the metadata of the Cargo project with the features selected by `matches`
+fn metadata(matches: &ArgMatches) -> Result<Metadata> {
     let mut metadata_command = MetadataCommand::new();
     if let Some(features) = matches.get_many::<String>("features") {
         metadata_command.features(CargoOpt::SomeFeatures(
@@ -291,164 +1047,1409 @@ pub fn run(tool: Tool, matches: ArgMatches) -> Result<i32> {
     if matches.get_flag("all-features") {
         metadata_command.features(CargoOpt::AllFeatures);
     }
+    if let Some(manifest_path) = matches.get_one::<String>("manifest-path") {
+        metadata_command.manifest_path(manifest_path);
+    }
+    // The target directory of the metadata tells where the artifacts of the different targets are
+    if let Some(target_dir) = matches.get_one::<String>("target-dir") {
+        metadata_command.env("CARGO_TARGET_DIR", target_dir);
+    }
+
+    let mut other_options = vec![];
+    if let Some(config) = matches.get_many::<String>("config") {
+        other_options.extend(config.flat_map(|c| ["--config".to_owned(), c.to_owned()]));
+    }
+    // Like `cargo build`, `cargo metadata` may have to update the lock file or fetch dependencies
+    for flag in ["frozen", "locked", "offline"] {
+        if matches.get_flag(flag) {
+            other_options.push(format!("--{flag}"));
+        }
+    }
+    metadata_command.other_options(other_options);
+
     let metadata = metadata_command.exec()?;
     if metadata.workspace_members.is_empty() {
         bail!("Unable to find workspace members");
     }
 
-    let mut tool_args = vec![];
-    if let Some(args) = matches.get_many::<String>("args") {
-        tool_args.extend(args.map(|s| s.as_str()));
+    Ok(metadata)
+}
+
+pub fn run(tool: Tool, matches: ArgMatches) -> Result<i32> {
+    let path = match matches.get_one::<String>("output") {
+        Some(path) => path.clone(),
+        None => {
+            let stdout = io::stdout();
+            let terminal = stdout.is_terminal();
+            return run_to(tool, matches, terminal, &mut stdout.lock());
+        }
+    };
+
+    let mut out =
+        BufWriter::new(File::create(&path).with_context(|| format!("Failed to create {path}"))?);
+    // Flush before looking at the result to keep the partial output of a tool that failed
+    let result = run_to(tool, matches, false, &mut out);
+    out.flush()
+        .with_context(|| format!("Failed to write {path}"))?;
+    result
+}
+
+/// Like `run` but writes the output of the tool to `out` rather than to stdout. The output of
+/// `cargo build` still goes to stdout, or to stderr with `cargo size --format json`.
+pub fn run_with_output<W: Write>(tool: Tool, matches: ArgMatches, out: &mut W) -> Result<i32> {
+    run_to(tool, matches, false, out)
+}
+
+// `terminal` tells whether `out` is a terminal, which `--watch` clears before each run
+fn run_to(tool: Tool, matches: ArgMatches, terminal: bool, out: &mut impl Write) -> Result<i32> {
+    if tool == Tool::Size && matches.get_flag("watch") {
+        return watch::run(tool, &matches, &metadata(&matches)?, terminal, out);
     }
 
-    let tool_help = tool_args.first() == Some(&"--help");
+    run_once(tool, matches, out)
+}
 
-    let target_artifact = if tool.needs_build() && !tool_help {
-        cargo_build(&matches, &metadata)?
+fn run_once(tool: Tool, matches: ArgMatches, out: &mut impl Write) -> Result<i32> {
+    // The tools that can't build the project don't need to run within a Cargo project
+    let metadata = if tool.can_build() {
+        Some(metadata(&matches)?)
     } else {
         None
     };
 
-    let mut lltool = Command::new(format!("rust-{}", tool.name()));
-
-    if tool == Tool::Objdump {
-        let ctxt = if let Some(artifact) = &target_artifact {
-            Context::from_artifact(metadata, artifact)?
-        } else {
-            Context::from_flag(
-                metadata,
-                matches.get_one::<String>("target").map(|s| s.as_str()),
-            )?
-        };
+    let env_args = env_flags(tool)?;
+    let mut tool_args = env_args.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+    let tool_help = matches
+        .get_many::<String>("args")
+        .and_then(|mut args| args.next())
+        .is_some_and(|arg| arg == "--help");
+    if let Some(args) = matches.get_many::<String>("args") {
+        tool_args.extend(args.map(|s| s.as_str()));
+    }
 
-        let arch_name = llvm::arch_name(&ctxt.cfg, &ctxt.target);
+    // `cargo cov` builds (an instrumented) executable only if asked to
+    let build = tool.needs_build()
+        || (tool == Tool::Cov
+            && ["show", "bin", "example", "test", "bench", "file"]
+                .iter()
+                .any(|arg| matches.contains_id(arg)));
+    // Only the tools that can build have a `--file` flag
+    let explicit_file = metadata
+        .as_ref()
+        .and_then(|_| matches.get_one::<Utf8PathBuf>("file"));
+    let mut artifacts = match (&metadata, explicit_file) {
+        (Some(_), Some(path)) if build && !tool_help => {
+            if !path.is_file() {
+                bail!("{path} is not a file");
+            }
 
-        if arch_name == "thumb" {
-            // `-arch-name=thumb` doesn't produce the right output so instead we pass
-            // `-triple=$target`, which contains more information about the target
-            lltool.args(["--triple", &ctxt.target]);
-        } else {
-            lltool.args(&[format!("--arch-name={arch_name}")]);
+            // The tools run in the directory of the file
+            let path = Utf8PathBuf::try_from(env::current_dir()?)?.join(path);
+            vec![ArtifactFile {
+                path,
+                // It's up to the user to pass an executable to e.g. `cargo cov --show`
+                executable: true,
+                manifest_dir: None,
+            }]
         }
-    }
-
-    // Extra flags
-    if let Tool::Readobj = tool {
-        // The default output style of `readobj` is JSON-like, which is not user friendly, so we
-        // change it to the human readable GNU style
-        lltool.arg("--elf-output-style=GNU");
-    }
+        (Some(metadata), None) if build && !tool_help => {
+            check_virtual_manifest(&matches, metadata)?;
 
-    if tool.needs_build() {
-        // Artifact
-        if let Some(artifact) = &target_artifact {
-            let file = match &artifact.executable {
-                // Example and bins have an executable
-                Some(val) => val,
-                // Libs have an rlib and an rmeta. We want the rlib, which always
-                // comes first in the filenames array after some quick testing.
-                //
-                // We could instead look for files ending in .rlib, but that would
-                // fail for cdylib and other fancy crate kinds.
-                None => &artifact.filenames[0],
+            let fresh = if matches.get_flag("if-changed") {
+                fresh_artifact(tool, &matches, metadata)?
+            } else {
+                None
             };
 
-            match tool {
-                // Tools that don't need a build
-                Tool::Ar | Tool::As | Tool::Cov | Tool::Lld | Tool::Profdata => {}
-                // for some tools we change the CWD (current working directory) and
-                // make the artifact path relative. This makes the path that the
-                // tool will print easier to read. e.g. `libfoo.rlib` instead of
-                // `/home/user/rust/project/target/$T/debug/libfoo.rlib`.
-                Tool::Objdump | Tool::Nm | Tool::Readobj | Tool::Size => {
-                    lltool
-                        .current_dir(file.parent().unwrap())
-                        .arg(file.file_name().unwrap());
+            match fresh {
+                Some(artifact) => {
+                    if matches.get_count("verbose") > 0 {
+                        eprintln!("{} is up to date, skipping the build", artifact.path);
+                    }
+                    vec![artifact]
                 }
-                Tool::Objcopy | Tool::Strip => {
-                    lltool.arg(file);
+                None => {
+                    let artifacts = cargo_build(tool, &matches, metadata)?
+                        .into_iter()
+                        .map(ArtifactFile::from)
+                        .collect::<Vec<_>>();
+
+                    // For the next `--if-changed` to tell whether it would build the same
+                    if matches.get_flag("if-changed") {
+                        let fingerprint = build_fingerprint(tool, &matches);
+                        for artifact in &artifacts {
+                            let path = fingerprint_path(&artifact.path);
+                            fs::write(&path, &fingerprint)
+                                .with_context(|| format!("Failed to write {path}"))?;
+                        }
+                    }
+
+                    artifacts
                 }
             }
         }
+        _ => vec![],
+    };
+
+    if artifacts.len() <= 1 {
+        return run_tool(
+            tool,
+            &matches,
+            metadata.as_ref(),
+            artifacts.first(),
+            &tool_args,
+            out,
+        );
     }
 
-    // User flags
-    lltool.args(&tool_args);
+    // `--workspace` and `--all-targets` can select several artifacts, the tool runs on each of them,
+    // in the same order from one run to the next
+    artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+    let header = !(matches.get_flag("no-run-tool") || matches.get_flag("print-artifact"));
+    let mut code = 0;
+    for (i, artifact) in artifacts.iter().enumerate() {
+        if header {
+            if i > 0 && !json_output(tool, &matches) {
+                writeln!(out)?;
+            }
+            // Keeps the JSON alone on stdout
+            if json_output(tool, &matches) {
+                eprintln!("{}:", artifact.path);
+            } else {
+                writeln!(out, "{}:", artifact.path)?;
+            }
+        }
 
-    if matches.get_count("verbose") > 0 {
-        eprintln!("{lltool:?}");
+        let artifact_code = run_tool(
+            tool,
+            &matches,
+            metadata.as_ref(),
+            Some(artifact),
+            &tool_args,
+            out,
+        )?;
+        if code == 0 {
+            code = artifact_code;
+        }
     }
+    Ok(code)
+}
 
-    let stdout = io::stdout();
-    let mut stdout = stdout.lock();
+/// Copies the output of a tool to `out` as it comes, demangling it line by line if `demangle`
+fn stream(mut output: impl BufRead, demangle: bool, out: &mut impl Write) -> io::Result<()> {
+    if demangle {
+        let mut demangler = postprocess::Demangler::default();
+        let mut line = vec![];
+        while output.read_until(b'\n', &mut line)? > 0 {
+            out.write_all(&demangler.demangle(&line))?;
+            line.clear();
+        }
+    } else {
+        io::copy(&mut output, out)?;
+    }
 
-    let output = lltool.stderr(Stdio::inherit()).output()?;
+    Ok(())
+}
 
-    // post process output
-    let processed_output = match tool {
-        Tool::Ar
-        | Tool::As
-        | Tool::Cov
-        | Tool::Lld
-        | Tool::Objcopy
-        | Tool::Profdata
-        | Tool::Strip => output.stdout.into(),
-        Tool::Nm | Tool::Objdump | Tool::Readobj => postprocess::demangle(&output.stdout),
-        Tool::Size => postprocess::size(&output.stdout),
+/// The `memory.x` linker script of the `artifact`, which is looked up from the directory of its
+/// package, like the build scripts of the `cortex-m-rt` family find it, or else from the root of
+/// the workspace
+fn memory_x(
+    metadata: Option<&Metadata>,
+    artifact: Option<&ArtifactFile>,
+) -> Result<Option<PathBuf>> {
+    let start = match (artifact.and_then(|a| a.manifest_dir.as_ref()), metadata) {
+        (Some(dir), _) => dir.clone().into_std_path_buf(),
+        (None, Some(metadata)) => metadata.workspace_root.clone().into_std_path_buf(),
+        (None, None) => env::current_dir()?,
     };
 
-    stdout.write_all(&processed_output)?;
+    Ok(search(&start, "memory.x").map(|dir| dir.join("memory.x")))
+}
 
-    if output.status.success() {
-        Ok(0)
-    } else {
-        Ok(output.status.code().unwrap_or(1))
+/// Runs `tool` on the `artifact`, if the tool inspects one
+fn run_tool(
+    tool: Tool,
+    matches: &ArgMatches,
+    metadata: Option<&Metadata>,
+    artifact: Option<&ArtifactFile>,
+    mut tool_args: &[&str],
+    out: &mut impl Write,
+) -> Result<i32> {
+    let file = artifact.map(|artifact| &artifact.path);
+
+    // The build succeeded, or we would have bailed
+    if metadata.is_some() && matches.get_flag("no-run-tool") {
+        return Ok(0);
     }
-}
 
-fn cargo_build(matches: &ArgMatches, metadata: &Metadata) -> Result<Option<Artifact>> {
-    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
-    let mut cargo = Command::new(cargo);
-    cargo.arg("build");
+    if metadata.is_some() && matches.get_flag("print-artifact") {
+        match file {
+            Some(file) => writeln!(out, "{file}")?,
+            None => bail!("`--print-artifact` needs a tool that inspects an artifact"),
+        }
+        return Ok(0);
+    }
 
-    let (build_type, verbose) = cargo_build_args(matches, &mut cargo);
-    let quiet = matches.get_flag("quiet");
+    // The tool only has to be installed to run it, not for `--dry-run`
+    let mut lltool = Command::new(tool.path()?);
 
-    cargo.arg("--message-format=json");
-    cargo.stdout(Stdio::piped());
+    if let (Tool::Objdump, Some(metadata)) = (tool, metadata) {
+        let ctxt = artifact_context(matches, metadata, file)?;
 
-    if verbose > 0 {
-        eprintln!("{cargo:?}");
-    }
+        let arch_override = matches.get_one::<String>("arch-name");
+        let arch_name = match arch_override {
+            Some(arch_name) => arch_name.as_str(),
+            None => llvm::arch_name(&ctxt.arch, &ctxt.endian, &ctxt.target),
+        };
 
-    let mut child = cargo.spawn()?;
-    let stdout = BufReader::new(child.stdout.take().expect("Pipe to cargo process failed"));
+        let user_arch = tool_args.iter().any(|arg| {
+            ["--triple", "-triple", "--arch-name", "-arch-name"]
+                .iter()
+                .any(|flag| {
+                    arg.strip_prefix(flag)
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('='))
+                })
+        });
+
+        if let Some(file) = file.filter(|_| !user_arch && matches.get_flag("arch-check")) {
+            check_arch(arch_name, file.as_std_path());
+        }
+
+        match arch_override {
+            _ if user_arch || arch_name.is_empty() => {
+                // The user picked the target themselves, don't pass a second (conflicting) one
+            }
+            // `-arch-name=thumb` doesn't produce the right output
+            Some(arch_name) if arch_name != "thumb" => {
+                lltool.arg(format!("--arch-name={arch_name}"));
+            }
+            _ => {
+                // Unlike the arch, e.g. `-arch-name=arm` which fails to decode the instructions of
+                // ARMv7-R cores, the triple tells the endianness, the ABI and the sub-arch
+                lltool.args(["--triple", &llvm::triple(&ctxt.target)]);
+            }
+        }
 
-    // Note: We call `collect` to ensure we don't block stdout which could prevent the process from exiting
-    let messages = Message::parse_stream(stdout).collect::<Vec<_>>();
+        if matches.get_flag("source") {
+            lltool.arg("--source");
+            if let Some(file) = file {
+                warn_if_no_debug_info(file.as_std_path());
+            }
+        }
 
-    let status = child.wait()?;
-    if !status.success() {
-        bail!("Failed to parse crate metadata");
+        if let (Some(function), Some(file)) = (matches.get_one::<String>("function"), file) {
+            let symbols = symbols::read(file.as_std_path())?;
+            let symbol = symbols::resolve(&symbols, function)?;
+            lltool.arg(format!("--disassemble-symbols={}", symbol.name));
+        }
+
+        // The CPU and features the artifact was built for, unless the user picked them
+        let (target_cpu, target_features) = if matches.get_flag("no-target-cpu") {
+            (None, None)
+        } else {
+            llvm::cpu_flags(&rustflags(metadata, &ctxt.target)?)
+        };
+        let user_flag = |flag: &str| {
+            tool_args.iter().any(|arg| {
+                arg.trim_start_matches('-')
+                    .strip_prefix(flag)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('='))
+            })
+        };
+
+        match matches.get_one::<String>("mcpu") {
+            Some(mcpu) => {
+                lltool.arg(format!("--mcpu={mcpu}"));
+            }
+            None => {
+                if let Some(mcpu) = target_cpu.filter(|_| !user_flag("mcpu")) {
+                    lltool.arg(format!("--mcpu={mcpu}"));
+                }
+            }
+        }
+        if let Some(mattr) = target_features.filter(|_| !user_flag("mattr")) {
+            lltool.arg(format!("--mattr={mattr}"));
+        }
+
+        if matches.get_flag("no-aliases") {
+            lltool.arg("--disassembler-options=no-aliases");
+        }
+
+        if matches.get_flag("imm-hex") {
+            lltool.arg("--print-imm-hex");
+        }
+
+        if matches.get_flag("disassemble-all") {
+            lltool.arg("--disassemble-all");
+        }
+
+        if matches.get_flag("wide") {
+            lltool.arg("--wide");
+        }
     }
 
-    let mut target_artifact: Option<Artifact> = None;
-    for message in messages {
-        match message? {
-            Message::CompilerArtifact(artifact) => {
-                if metadata.workspace_members.contains(&artifact.package_id)
-                    && build_type.matches(&artifact)
+    if json_output(tool, matches) {
+        // The JSON is made from the System V table
+        lltool.arg("-A");
+    }
+
+    if tool == Tool::Nm {
+        if matches.get_flag("dynamic") {
+            lltool.arg("--dynamic");
+        }
+
+        if let Some(format) = matches.get_one::<String>("nm-format") {
+            lltool.arg(format!("--format={format}"));
+        }
+
+        // The sizes are only printed on demand
+        let mut sort_keys = matches.get_many::<String>("sort").into_iter().flatten();
+        if sort_keys.any(|key| key == "size") {
+            lltool.arg("--print-size");
+        }
+
+        if matches.get_flag("line-numbers") {
+            lltool.arg("--line-numbers");
+            if let Some(file) = file {
+                warn_if_no_debug_info(file.as_std_path());
+            }
+        }
+    }
+
+    if tool == Tool::Objcopy {
+        if let Some(file) = file.filter(|_| matches.contains_id("split-debug")) {
+            let debug_file = match matches.get_one::<String>("split-debug") {
+                Some(debug_file) => debug_file.clone(),
+                None => format!("{file}.debug"),
+            };
+            let verbose = matches.get_count("verbose") > 0;
+            let compress = matches
+                .get_one::<String>("compress-debug")
+                .map(|s| s.as_str());
+            return split_debug(
+                file.as_std_path(),
+                &debug_file,
+                compress,
+                tool_args,
+                verbose,
+                matches.get_flag("dry-run"),
+                out,
+            );
+        }
+
+        if let Some(algorithm) = matches.get_one::<String>("compress-debug") {
+            lltool.arg(format!("--compress-debug-sections={algorithm}"));
+        }
+
+        if let Some(changes) = matches.get_many::<String>("change-lma") {
+            for change in changes {
+                lltool.args(["--change-section-lma", change]);
+            }
+        }
+
+        if let Some(flags) = matches.get_many::<String>("set-section-flags") {
+            for flags in flags {
+                lltool.args(["--set-section-flags", flags]);
+            }
+        }
+
+        if let (Some(entry), Some(file)) = (matches.get_one::<String>("entry"), file) {
+            let symbols = symbols::read(file.as_std_path())?;
+            let symbol = symbols::resolve(&symbols, entry)?;
+            lltool.arg(format!("--set-start={:#x}", symbol.address));
+        }
+
+        if let (Some(redefinitions), Some(file)) =
+            (matches.get_many::<String>("redefine-sym"), file)
+        {
+            let symbols = symbols::read(file.as_std_path())?;
+            for redefinition in redefinitions {
+                // Checked by `parse_redefine_sym`
+                let (old, new) = redefinition.rsplit_once('=').unwrap();
+                let symbol = symbols::resolve(&symbols, old)?;
+                lltool.args(["--redefine-sym", &format!("{}={new}", symbol.name)]);
+            }
+        }
+
+        // The flags are named after the ones of objcopy
+        let visibility = ["weaken-symbol", "localize-symbol", "globalize-symbol"];
+        if let (true, Some(file)) = (visibility.iter().any(|id| matches.contains_id(id)), file) {
+            let symbols = symbols::read(file.as_std_path())?;
+            for flag in visibility {
+                for name in matches.get_many::<String>(flag).into_iter().flatten() {
+                    let symbol = symbols::resolve(&symbols, name)?;
+                    lltool.arg(format!("--{flag}={}", symbol.name));
+                }
+            }
+        }
+
+        let explicit_format = tool_args.iter().any(|arg| {
+            ["-O", "--output-target", "-I", "--input-target"]
+                .iter()
+                .any(|flag| arg.starts_with(flag))
+        });
+
+        // Without `-O` the output is an ELF file too, spell out its format so the output matches
+        // the target even when objcopy can't tell it from the input
+        if let (false, Some(metadata), Some(file)) = (explicit_format, metadata, file) {
+            // This is only a default, if the artifact or the target can't be read nothing is passed
+            if let (Ok(elf), Ok(ctxt)) = (
+                elf::Elf::read(file.as_std_path()),
+                artifact_context(matches, metadata, Some(file)),
+            ) {
+                let arch_name = llvm::arch_name(&ctxt.arch, &ctxt.endian, &ctxt.target);
+                if let Some(output_target) =
+                    llvm::elf_output_target(arch_name, elf.header.is64, elf.header.big_endian)
                 {
-                    if target_artifact.is_some() {
-                        bail!("Can only have one matching artifact but found several");
+                    lltool.arg(format!("--output-target={output_target}"));
+                }
+            }
+        }
+    }
+
+    // Extra flags
+    if let (Tool::Readobj, Some(file)) = (tool, file) {
+        if matches.get_flag("header-json") {
+            return print_header_json(file.as_std_path(), out);
+        }
+    }
+
+    if let (Tool::Size, Some(file)) = (tool, file) {
+        if let Some(baseline) = matches.get_one::<PathBuf>("diff") {
+            let current = size_sysv(file.as_std_path())?;
+            let baseline = size_sysv(baseline)?;
+            let all = matches.get_flag("diff-all");
+            write!(out, "{}", postprocess::size_diff(&current, &baseline, all)?)?;
+            return Ok(0);
+        }
+
+        if matches.get_flag("regions") {
+            match memory_x(metadata, artifact)? {
+                Some(path) => {
+                    let regions = memory::read(&path)?;
+                    let elf = elf::Elf::read(file.as_std_path())?;
+                    let human = matches.get_flag("human");
+                    write!(out, "{}", postprocess::regions(&elf, &regions, human))?;
+                    return Ok(0);
+                }
+                None => eprintln!("warning: no memory.x found, printing the sections instead"),
+            }
+        }
+    }
+
+    if let Tool::Readobj = tool {
+        // The default output style of `readobj` is JSON-like, which is not user friendly, so we
+        // change it to the human readable GNU style, unless the user picked one
+        let user_style = tool_args.iter().any(|arg| {
+            ["--elf-output-style", "-elf-output-style"]
+                .iter()
+                .any(|flag| {
+                    arg.strip_prefix(flag)
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('='))
+                })
+        });
+        if !user_style {
+            lltool.arg("--elf-output-style=GNU");
+        }
+
+        if matches.get_flag("symbol-versions") {
+            lltool.arg("--version-info");
+        }
+
+        // readobj labels each dump with its kind and section
+        for section in matches.get_many::<String>("dump").into_iter().flatten() {
+            lltool.arg(format!("--string-dump={section}"));
+            lltool.arg(format!("--hex-dump={section}"));
+        }
+    }
+
+    if tool.needs_build() {
+        // Artifact
+        if let Some(file) = file {
+            match tool {
+                // Tools that don't need a build
+                Tool::Ar
+                | Tool::As
+                | Tool::Cov
+                | Tool::Cxxfilt
+                | Tool::Lld
+                | Tool::Mca
+                | Tool::Profdata
+                | Tool::Ranlib => {}
+                // for some tools we change the CWD (current working directory) and
+                // make the artifact path relative. This makes the path that the
+                // tool will print easier to read. e.g. `libfoo.rlib` instead of
+                // `/home/user/rust/project/target/$T/debug/libfoo.rlib`.
+                Tool::Objdump | Tool::Nm | Tool::Readobj | Tool::Size | Tool::Strings => {
+                    lltool
+                        .current_dir(file.parent().unwrap())
+                        .arg(file.file_name().unwrap());
+                }
+                // The positional arguments of `addr2line` are the addresses
+                Tool::Addr2line => {
+                    lltool
+                        .current_dir(file.parent().unwrap())
+                        .arg("-e")
+                        .arg(file.file_name().unwrap());
+                }
+                Tool::Objcopy | Tool::Strip => {
+                    lltool.arg(file);
+                }
+                Tool::Symbolizer => {
+                    lltool.arg(format!("--obj={file}"));
+                }
+            }
+        }
+    }
+
+    if tool == Tool::Cov {
+        // The user's profile takes precedence over the one we find
+        let instr_profile = tool_args
+            .iter()
+            .any(|arg| arg.trim_start_matches('-').starts_with("instr-profile"));
+        if let (Some(paths), Some(artifact), Some(metadata)) =
+            (matches.get_many::<String>("show"), artifact, metadata)
+        {
+            let executable = &artifact.path;
+            if !artifact.executable {
+                bail!("`--show` needs an executable, select one with `--bin` or `--test`");
+            }
+
+            lltool.arg("show").arg(executable);
+            if !instr_profile {
+                lltool.arg(format!(
+                    "--instr-profile={}",
+                    find_profdata(executable, metadata)?
+                ));
+            }
+            lltool.arg("--show-regions").args(paths);
+        } else if let Some(artifact) = artifact {
+            if !artifact.executable {
+                bail!("`cargo cov` needs an executable, select one with `--bin` or `--test`");
+            }
+
+            // The executable is the first positional argument of these subcommands
+            match tool_args.split_first() {
+                Some((&subcommand, rest)) if ["show", "report", "export"].contains(&subcommand) => {
+                    lltool.arg(subcommand).arg(&artifact.path);
+                    tool_args = rest;
+
+                    // Unlike `--show`, go on without a profile for `llvm-cov` to complain about
+                    let profdata = metadata
+                        .filter(|_| !instr_profile)
+                        .and_then(|metadata| profdata(&artifact.path, metadata));
+                    if let Some(profdata) = profdata {
+                        lltool.arg(format!("--instr-profile={profdata}"));
+                    }
+                }
+                _ => bail!(
+                    "Pass the `llvm-cov` subcommand to run on {} after `--`, e.g. `-- report`",
+                    artifact.path
+                ),
+            }
+        }
+    }
+
+    if tool == Tool::Profdata {
+        if let Some(dir) = matches.get_one::<PathBuf>("merge-dir") {
+            lltool.args(["merge", "-sparse"]).args(profraw_files(dir)?);
+            if !has_output(tool_args) {
+                lltool.args(["-o", "default.profdata"]);
+            }
+        }
+    }
+
+    if let (Tool::Strip, Some(file)) = (tool, file) {
+        let in_place = matches.get_flag("in-place");
+        if in_place && has_output(tool_args) {
+            bail!("`--in-place` conflicts with passing `-o` to `llvm-strip`");
+        }
+
+        // Without `-o` `llvm-strip` modifies its input too, which ruins an rlib for the crates
+        // that link to it
+        if !has_output(tool_args) {
+            if file.extension() == Some("rlib") {
+                bail!(
+                    "Refusing to strip {file} in place, pass `-- -o PATH` to write a stripped copy"
+                );
+            }
+            // The same as no `-o` at all, but shows where the output goes, e.g. with `--dry-run`
+            if in_place {
+                lltool.arg("-o").arg(file);
+            }
+        }
+    }
+
+    // User flags
+    lltool.args(tool_args);
+
+    if matches.get_flag("dry-run") {
+        writeln!(out, "{lltool:?}")?;
+        return Ok(0);
+    }
+    tool.resolve()?;
+
+    if matches.get_count("verbose") > 0 {
+        eprintln!("{lltool:?}");
+    }
+
+    let stderr = match matches.get_one::<String>("capture-stderr") {
+        Some(path) => File::create(path)
+            .with_context(|| format!("Failed to create {path}"))?
+            .into(),
+        None => Stdio::inherit(),
+    };
+    // Tools that don't inspect an artifact may read their input from stdin, e.g. `cxxfilt` or
+    // `mca`, as do the ones resolving addresses if none are given as arguments
+    if !tool.needs_build() || matches!(tool, Tool::Addr2line | Tool::Symbolizer) {
+        lltool.stdin(Stdio::inherit());
+    }
+    lltool.stderr(stderr);
+
+    // The output of the tools whose output needs no post processing, or only line by line, is
+    // streamed rather than buffered, as it can get large, e.g. the disassembly of a whole firmware
+    let streamed = match tool {
+        Tool::Ar
+        | Tool::As
+        | Tool::Cov
+        | Tool::Cxxfilt
+        | Tool::Lld
+        | Tool::Mca
+        | Tool::Objcopy
+        | Tool::Profdata
+        | Tool::Ranlib
+        | Tool::Strings
+        | Tool::Strip => Some(false),
+        // `--source-dedup` looks at the previous lines
+        Tool::Objdump if matches.get_flag("source-dedup") => None,
+        Tool::Addr2line | Tool::Objdump | Tool::Symbolizer => {
+            Some(!matches.get_flag("no-demangle"))
+        }
+        Tool::Nm | Tool::Readobj | Tool::Size => None,
+    };
+    if let Some(demangle) = streamed {
+        let mut child = lltool.stdout(Stdio::piped()).spawn()?;
+        let stdout = BufReader::new(child.stdout.take().expect("Pipe to the tool failed"));
+        stream(stdout, demangle, out)?;
+
+        let status = child.wait()?;
+        return Ok(if status.success() {
+            0
+        } else {
+            status.code().unwrap_or(1)
+        });
+    }
+
+    let output = lltool.output()?;
+
+    // post process output
+    let processed_output = match tool {
+        // Streamed above
+        Tool::Addr2line
+        | Tool::Ar
+        | Tool::As
+        | Tool::Cov
+        | Tool::Cxxfilt
+        | Tool::Lld
+        | Tool::Mca
+        | Tool::Objcopy
+        | Tool::Profdata
+        | Tool::Ranlib
+        | Tool::Strings
+        | Tool::Strip
+        | Tool::Symbolizer => unreachable!(),
+        Tool::Nm => {
+            let options = postprocess::NmOptions {
+                demangle: !matches.get_flag("no-demangle"),
+                names_only: matches.get_flag("names-only"),
+                symbols: match (matches.get_one::<String>("section"), file) {
+                    (Some(section), Some(file)) => {
+                        Some(section_symbols(file.as_std_path(), section)?)
                     }
+                    _ => None,
+                },
+                // a `--format` after `--` comes last on the command line so it wins
+                format: postprocess::NmFormat::from_args(
+                    matches
+                        .get_one::<String>("nm-format")
+                        .and_then(|format| postprocess::NmFormat::from_name(format))
+                        .unwrap_or(postprocess::NmFormat::Bsd),
+                    tool_args,
+                ),
+                sort: matches
+                    .get_many::<String>("sort")
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|key| postprocess::SymbolSort::from_name(key))
+                    .collect(),
+                reverse: matches.get_flag("reverse-sort"),
+            };
+
+            postprocess::nm(&output.stdout, &options)
+        }
+        // Only buffered with `--source-dedup`
+        Tool::Objdump => {
+            let output = if matches.get_flag("no-demangle") {
+                output.stdout.into()
+            } else {
+                postprocess::demangle(&output.stdout)
+            };
+            postprocess::dedup_source(&output).into_owned().into()
+        }
+        Tool::Readobj if matches.get_flag("no-demangle") => output.stdout.into(),
+        Tool::Readobj => postprocess::readobj(&output.stdout),
+        Tool::Size if output.status.success() && json_output(tool, matches) => {
+            // The sections of other kinds of artifacts are not classified
+            let sections = file
+                .and_then(|file| elf::Elf::read(file.as_std_path()).ok())
+                .map(|elf| elf.sections);
+            let totals_only = matches.get_flag("totals-only");
+            postprocess::size_json(&output.stdout, sections.as_deref(), totals_only)?.into()
+        }
+        Tool::Size => {
+            let options = postprocess::SizeOptions {
+                sort: matches
+                    .get_one::<String>("sort")
+                    .map(|sort| match sort.as_str() {
+                        "size" => postprocess::SectionSort::Size,
+                        "name" => postprocess::SectionSort::Name,
+                        _ => postprocess::SectionSort::Vma,
+                    }),
+                group_by_prefix: matches.get_flag("group-by-prefix"),
+                human: matches.get_flag("human"),
+                totals_only: matches.get_flag("totals-only"),
+                segments: match file {
+                    Some(file) if matches.get_flag("lma") => {
+                        Some(elf::Elf::read(file.as_std_path())?.segments)
+                    }
+                    _ => None,
+                },
+            };
+
+            postprocess::size(&output.stdout, &options)
+        }
+    };
+
+    out.write_all(&processed_output)?;
+
+    if output.status.success() {
+        Ok(0)
+    } else {
+        Ok(output.status.code().unwrap_or(1))
+    }
+}
+
+/// The context of the inspected `file`, or of the artifacts of the selected target if there's none
+fn artifact_context(
+    matches: &ArgMatches,
+    metadata: &Metadata,
+    file: Option<&Utf8PathBuf>,
+) -> Result<Context> {
+    // The path of an artifact built by cargo tells its target, unlike the one of a `--file`
+    let built = file.filter(|_| !matches.contains_id("file"));
+    match (built, matches.get_one::<String>("target")) {
+        // The artifacts of a custom target are in a directory named after its spec, which
+        // doesn't tell where the spec is
+        (Some(_), Some(target)) if target.ends_with(".json") => Context::from_target_name(target),
+        (Some(file), _) => Context::from_artifact(metadata, file),
+        (None, target) => {
+            // Like cargo, `--config build.target=..` takes precedence over the environment and
+            // the config files
+            let target = match target {
+                Some(target) => Some(target.clone()),
+                None => config_arg_target(matches)?,
+            };
+            Context::from_flag(metadata, target.as_deref())
+        }
+    }
+}
+
+/// Runs `size -A` on `file`, returning its output
+fn size_sysv(file: &Path) -> Result<Vec<u8>> {
+    let output = tool_command(Tool::Size)?
+        .arg("-A")
+        .arg(file)
+        .stderr(Stdio::inherit())
+        .output()?;
+
+    if !output.status.success() {
+        bail!("Failed to read the sections of {}", file.display());
+    }
+
+    Ok(output.stdout)
+}
+
+/// The name of the environment variable with the default flags of `tool`, e.g. `CARGO_NM_FLAGS`
+fn env_flags_var(tool: Tool) -> String {
+    format!("CARGO_{}_FLAGS", tool.name().to_uppercase())
+}
+
+/// The default flags of `tool` set in the environment, which come before the ones after `--`
+fn env_flags(tool: Tool) -> Result<Vec<String>> {
+    let var = env_flags_var(tool);
+    match env::var(&var) {
+        Ok(flags) => split_flags(&flags).with_context(|| format!("Failed to parse `{var}`")),
+        Err(_) => Ok(vec![]),
+    }
+}
+
+/// Splits `s` into words like a shell does, minus the expansions: words are separated by
+/// whitespace, which can be quoted with `'` or `"` or escaped with `\`
+fn split_flags(s: &str) -> Result<Vec<String>> {
+    let mut words = vec![];
+    let mut word = None::<String>;
+    let mut quote = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => bail!("trailing `\\`"),
+            },
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if let Some(quote) = quote {
+        bail!("unterminated `{quote}` quote");
+    }
+    words.extend(word);
+
+    Ok(words)
+}
+
+/// Whether the user passes the output file, `-o`, of the tool
+fn has_output(tool_args: &[&str]) -> bool {
+    tool_args
+        .iter()
+        .any(|arg| *arg == "-o" || arg.starts_with("-o=") || arg.starts_with("--output"))
+}
+
+/// The `*.profraw` files in `dir`, sorted by name
+fn profraw_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "profraw")
+            && path.is_file()
+        {
+            files.push(path);
+        }
+    }
+
+    if files.is_empty() {
+        bail!("There are no `.profraw` files in {}", dir.display());
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+// Where the `default.profdata` of `executable` is looked for, in order
+fn profdata_candidates(executable: &Utf8Path, metadata: &Metadata) -> [Utf8PathBuf; 2] {
+    [
+        executable.with_file_name("default.profdata"),
+        metadata.workspace_root.join("default.profdata"),
+    ]
+}
+
+/// The `default.profdata` of `executable`, either next to it or in the workspace root, if any
+fn profdata(executable: &Utf8Path, metadata: &Metadata) -> Option<Utf8PathBuf> {
+    profdata_candidates(executable, metadata)
+        .into_iter()
+        .find(|candidate| candidate.is_file())
+}
+
+/// Like `profdata` but errors with how to create the profile if there's none
+fn find_profdata(executable: &Utf8Path, metadata: &Metadata) -> Result<Utf8PathBuf> {
+    match profdata(executable, metadata) {
+        Some(profdata) => Ok(profdata),
+        None => bail!(
+            "Could not find the coverage profile of {executable}, merge the `.profraw` files into \
+             {} with `cargo profdata -- merge -sparse *.profraw -o default.profdata` or pass \
+             `-- --instr-profile=FILE`",
+            profdata_candidates(executable, metadata)[1]
+        ),
+    }
+}
+
+/// The names of the symbols `file` defines in `section` and its `section.*` subsections, i.e. what
+/// the sections are called in object files built with `-ffunction-sections`
+fn section_symbols(file: &Path, section: &str) -> Result<HashSet<String>> {
+    let elf = elf::Elf::read(file).context("`--section` only supports ELF artifacts")?;
+
+    let matches = |s: &elf::Section| {
+        s.name
+            .strip_prefix(section)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    };
+    if !elf.sections.iter().any(matches) {
+        bail!("{} has no `{section}` section", file.display());
+    }
+
+    Ok(elf
+        .symbols_in(matches)
+        .map(|symbol| symbol.name.clone())
+        .collect())
+}
+
+/// The `schema_version` of all the JSON outputs, bumped on breaking changes to any of them
+const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// Prints the ELF file header of `file` as a JSON object
+fn print_header_json(file: &Path, out: &mut impl Write) -> Result<i32> {
+    let header = elf::Elf::read(file)?.header;
+
+    let json = serde_json::json!({
+        "schema_version": JSON_SCHEMA_VERSION,
+        "class": if header.is64 { "ELF64" } else { "ELF32" },
+        "data": if header.big_endian { "big-endian" } else { "little-endian" },
+        "os_abi": header.os_abi,
+        "type": header.kind_name(),
+        "type_id": header.kind,
+        "machine": header.machine_name(),
+        "machine_id": header.machine,
+        "entry": header.entry,
+        "flags": header.flags,
+    });
+    writeln!(out, "{}", serde_json::to_string_pretty(&json)?)?;
+
+    Ok(0)
+}
+
+/// The command that runs the proxied `tool`. It runs the tool of the toolchain directly instead of
+/// going through the `rust-$tool` on `PATH`, which can be an unrelated (older) installation.
+fn tool_command(tool: Tool) -> Result<Command> {
+    Ok(Command::new(tool.resolve()?))
+}
+
+/// Moves the debug info of `file` into `debug_file`. This is the usual
+/// `objcopy --only-keep-debug`, `objcopy --strip-debug`, `objcopy --add-gnu-debuglink` sequence,
+/// `tool_args` are passed to the stripping step and the debug file is compressed with the
+/// `compress` algorithm, if any.
+fn split_debug(
+    file: &Path,
+    debug_file: &str,
+    compress: Option<&str>,
+    tool_args: &[&str],
+    verbose: bool,
+    dry_run: bool,
+    out: &mut impl Write,
+) -> Result<i32> {
+    let path = if dry_run {
+        Tool::Objcopy.path()?
+    } else {
+        Tool::Objcopy.resolve()?
+    };
+
+    let mut keep_debug = Command::new(&path);
+    keep_debug.arg("--only-keep-debug");
+    if let Some(algorithm) = compress {
+        keep_debug.arg(format!("--compress-debug-sections={algorithm}"));
+    }
+    keep_debug.arg(file).arg(debug_file);
+
+    let mut strip_debug = Command::new(&path);
+    strip_debug.arg("--strip-debug").args(tool_args).arg(file);
+
+    // This has to happen after stripping as the link contains a checksum of the debug file
+    let mut add_debuglink = Command::new(&path);
+    add_debuglink
+        .arg(format!("--add-gnu-debuglink={debug_file}"))
+        .arg(file);
+
+    for mut objcopy in [keep_debug, strip_debug, add_debuglink] {
+        if dry_run {
+            writeln!(out, "{objcopy:?}")?;
+            continue;
+        }
 
-                    target_artifact = Some(artifact);
+        if verbose {
+            eprintln!("{objcopy:?}");
+        }
+
+        let status = objcopy.status()?;
+        if !status.success() {
+            return Ok(status.code().unwrap_or(1));
+        }
+    }
+
+    if !dry_run {
+        writeln!(out, "{}", file.display())?;
+        writeln!(out, "{debug_file}")?;
+    }
+
+    Ok(0)
+}
+
+/// Building from a virtual manifest, i.e. a `Cargo.toml` with a `[workspace]` but no `[package]`,
+/// builds every workspace member so the artifact to inspect must be selected explicitly
+fn check_virtual_manifest(matches: &ArgMatches, metadata: &Metadata) -> Result<()> {
+    let selected = ["package", "bin", "example", "test", "bench"]
+        .iter()
+        .any(|arg| matches.contains_id(arg));
+    if selected || several_artifacts(matches) {
+        return Ok(());
+    }
+
+    let manifest_path = match matches.get_one::<String>("manifest-path") {
+        Some(manifest_path) => Path::new(manifest_path).to_owned(),
+        None => match search(&env::current_dir()?, "Cargo.toml") {
+            Some(dir) => dir.join("Cargo.toml"),
+            None => return Ok(()),
+        },
+    };
+    let manifest: toml::Value = parse(&manifest_path)?;
+
+    let workspace = match manifest.get("workspace") {
+        Some(workspace) if manifest.get("package").is_none() => workspace,
+        _ => return Ok(()),
+    };
+    let default_members = workspace
+        .get("default-members")
+        .and_then(|members| members.as_array());
+    if default_members.is_some_and(|members| members.len() == 1) {
+        return Ok(());
+    }
+
+    let members = metadata
+        .packages
+        .iter()
+        .filter(|package| metadata.workspace_members.contains(&package.id))
+        .map(|package| package.name.clone())
+        .collect::<Vec<_>>();
+
+    bail!(
+        "{} is a virtual manifest, select the package to inspect with `--package` or `--bin`. \
+         Workspace members: {}",
+        manifest_path.display(),
+        members.join(", ")
+    )
+}
+
+/// Matches `text` against a glob `pattern`, where `*` matches any (possibly empty) string and `?`
+/// any single character
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    // Position after the last `*` seen in the pattern and the text position it matched up to
+    let mut backtrack = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the `*` match one more character
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
                 }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Locates the artifact selected by `matches` without running `cargo build`, if it's newer than its
+/// manifest and all the sources listed in the dep-info file cargo writes next to it. Returns `None`
+/// if the artifact needs to be (re)built or can't be located up front, e.g. for tests whose file
+/// names contain a hash.
+fn fresh_artifact(
+    tool: Tool,
+    matches: &ArgMatches,
+    metadata: &Metadata,
+) -> Result<Option<ArtifactFile>> {
+    if ["test", "bench", "artifact-glob"]
+        .iter()
+        .any(|arg| matches.contains_id(arg))
+        || matches.get_flag("proc-macro")
+    {
+        return Ok(None);
+    }
+
+    let members = metadata
+        .packages
+        .iter()
+        .filter(|package| metadata.workspace_members.contains(&package.id))
+        .collect::<Vec<_>>();
+    let package = match matches.get_one::<String>("package") {
+        // `name` or `name@version`
+        Some(spec) => {
+            let name = spec.split('@').next().unwrap_or(spec);
+            members.iter().find(|package| package.name == name)
+        }
+        None => match metadata.root_package() {
+            Some(package) => members.iter().find(|member| member.id == package.id),
+            None if members.len() == 1 => members.first(),
+            None => None,
+        },
+    };
+    let Some(package) = package else {
+        return Ok(None);
+    };
+
+    let targets = |kind: &str| {
+        package
+            .targets
+            .iter()
+            .filter(|target| target.kind.iter().any(|k| k == kind))
+            .collect::<Vec<_>>()
+    };
+    let named = |kind, name: &str| targets(kind).into_iter().find(|target| target.name == name);
+    // The directory of the artifact relative to the profile directory, its name without the
+    // extension and whether it's an executable
+    let (dir, name, executable) = if matches.get_flag("lib") {
+        match targets("lib").into_iter().chain(targets("rlib")).next() {
+            Some(lib) => ("", format!("lib{}", lib.name.replace('-', "_")), false),
+            None => return Ok(None),
+        }
+    } else if let Some(name) = matches.get_one::<String>("bin") {
+        match named("bin", name) {
+            Some(bin) => ("", bin.name.clone(), true),
+            None => return Ok(None),
+        }
+    } else if let Some(name) = matches.get_one::<String>("example") {
+        match named("example", name) {
+            Some(example) => ("examples", example.name.clone(), true),
+            None => return Ok(None),
+        }
+    } else {
+        match *targets("bin") {
+            [bin] => ("", bin.name.clone(), true),
+            _ => return Ok(None),
+        }
+    };
+
+    let target = build_target(matches, metadata)?;
+
+    let profile = match matches.get_one::<String>("profile").map(|s| s.as_str()) {
+        Some("dev" | "test") | None if !matches.get_flag("release") => "debug",
+        Some("release" | "bench") | None => "release",
+        Some(profile) => profile,
+    };
+
+    let mut profile_dir = metadata.target_directory.clone();
+    if let Some(target) = &target {
+        // Custom targets are given as the path of their JSON spec
+        profile_dir.push(
+            target
+                .strip_suffix(".json")
+                .map_or(target.as_str(), |spec| {
+                    Utf8Path::new(spec).file_name().unwrap_or(spec)
+                }),
+        );
+    }
+    profile_dir.push(profile);
+
+    let path = if executable {
+        let triple = match &target {
+            Some(target) => target.clone(),
+            None => rustc_version::version_meta()?.host,
+        };
+        let extension = if triple.contains("windows") {
+            ".exe"
+        } else if triple.starts_with("wasm") {
+            ".wasm"
+        } else {
+            ""
+        };
+        profile_dir.join(dir).join(format!("{name}{extension}"))
+    } else {
+        profile_dir.join(dir).join(format!("{name}.rlib"))
+    };
+
+    let depinfo = profile_dir.join(dir).join(format!("{name}.d"));
+    if !depinfo.is_file() {
+        return Ok(None);
+    }
+
+    let mut deps = depinfo::read(depinfo.as_std_path(), metadata.workspace_root.as_std_path())?;
+    deps.push(package.manifest_path.clone().into());
+    let lockfile = metadata.workspace_root.join("Cargo.lock");
+    if lockfile.exists() {
+        deps.push(lockfile.into());
+    }
+    // The configuration, e.g. its `rustflags`
+    deps.extend(search_config(metadata.workspace_root.as_std_path()));
+    for config in matches.get_many::<String>("config").into_iter().flatten() {
+        if Path::new(config).is_file() {
+            deps.push(config.into());
+        }
+    }
+
+    // The sources didn't change, but the flags might have
+    let fingerprint = fs::read_to_string(fingerprint_path(&path)).ok();
+    if fingerprint.as_deref() == Some(build_fingerprint(tool, matches).as_str())
+        && depinfo::is_fresh(path.as_std_path(), deps.iter().map(|dep| dep.as_path()))
+    {
+        Ok(Some(ArtifactFile {
+            path,
+            executable,
+            manifest_dir: package.manifest_path.parent().map(|dir| dir.to_owned()),
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+/// What the artifact of `cargo build` depends on besides its sources, i.e. the flags and the
+/// environment variables that change how cargo builds it
+fn build_fingerprint(tool: Tool, matches: &ArgMatches) -> String {
+    let mut inputs = vec![];
+    for flag in ["release", "no-default-features", "all-features"] {
+        if matches.get_flag(flag) {
+            inputs.push(format!("--{flag}"));
+        }
+    }
+    for arg in ["profile", "target", "features", "config"] {
+        for value in matches.get_many::<String>(arg).into_iter().flatten() {
+            inputs.push(format!("--{arg}={value}"));
+        }
+    }
+
+    let mut vars = env::vars()
+        .filter(|(name, _)| {
+            name == "RUSTFLAGS"
+                || name == "CARGO_ENCODED_RUSTFLAGS"
+                || ["CARGO_BUILD_", "CARGO_PROFILE_", "CARGO_TARGET_"]
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+        })
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>();
+    vars.sort();
+    inputs.extend(vars);
+
+    // `cargo cov` instruments the build
+    if tool == Tool::Cov {
+        inputs.push("-Cinstrument-coverage".to_owned());
+    }
+
+    inputs.join("\n")
+}
+
+// Where the `build_fingerprint` of the last `--if-changed` build of `artifact` is kept
+fn fingerprint_path(artifact: &Utf8Path) -> Utf8PathBuf {
+    format!("{artifact}.binutils-fingerprint").into()
+}
+
+/// Whether the output of `cargo build` is hidden, it would get in the way of the output of
+/// `--print-artifact`
+fn quiet_build(matches: &ArgMatches) -> bool {
+    matches.get_flag("quiet")
+        || matches.get_flag("no-run-tool")
+        || matches.get_flag("print-artifact")
+}
+
+/// Builds the project, returning the selected artifact, or all the matching ones with
+/// `--workspace` and `--all-targets`
+fn cargo_build(tool: Tool, matches: &ArgMatches, metadata: &Metadata) -> Result<Vec<Artifact>> {
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let mut cargo = Command::new(cargo);
+    cargo.arg("build");
+
+    if tool == Tool::Cov {
+        // The environment variables replace the `rustflags` of the Cargo config so these have to
+        // be merged in here, `CARGO_ENCODED_RUSTFLAGS` also takes precedence over `RUSTFLAGS`
+        let target = match build_target(matches, metadata)? {
+            Some(target) => target,
+            None => rustc_version::version_meta()?.host,
+        };
+        let flags = coverage_rustflags(rustflags(metadata, &target)?);
+        cargo.env("CARGO_ENCODED_RUSTFLAGS", flags);
+    }
+
+    let (build_type, verbose) = cargo_build_args(matches, &mut cargo);
+    let quiet = quiet_build(matches);
+
+    cargo.arg(message_format(tool, matches));
+    cargo.stdout(Stdio::piped());
+
+    if verbose > 0 {
+        eprintln!("{cargo:?}");
+    }
+
+    let mut child = cargo.spawn()?;
+    let stdout = BufReader::new(child.stdout.take().expect("Pipe to cargo process failed"));
+
+    let artifact_glob = matches.get_one::<String>("artifact-glob");
+    let mut target_artifacts: Vec<Artifact> = vec![];
+    let mut skipped_proc_macro = false;
+    let mut parse_error = None;
+    // The messages are handled as they come so the diagnostics show up during the build. Note: We
+    // read the stream to the end, even after an error, to ensure we don't block stdout which
+    // could prevent the process from exiting
+    for message in Message::parse_stream(stdout) {
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                parse_error.get_or_insert(e);
+                continue;
+            }
+        };
+
+        match message {
+            Message::CompilerArtifact(mut artifact)
+                if metadata.workspace_members.contains(&artifact.package_id)
+                    && build_type.matches(&artifact)
+                    && artifact_glob.map_or(true, |pattern| {
+                        artifact.filenames.iter().any(|file| {
+                            file.file_name()
+                                .is_some_and(|name| glob_match(pattern, name))
+                        })
+                    }) =>
+            {
+                // Only keep the matching files, e.g. the `.a` of a lib that is both an rlib and a
+                // staticlib with `--artifact-glob '*.a'`
+                if let Some(pattern) = artifact_glob {
+                    artifact.filenames.retain(|file| {
+                        file.file_name()
+                            .is_some_and(|name| glob_match(pattern, name))
+                    });
+                }
+                target_artifacts.push(artifact);
             }
-            Message::CompilerMessage(msg) => {
-                if !quiet || verbose > 1 {
-                    if let Some(rendered) = msg.message.rendered {
+            Message::CompilerArtifact(artifact)
+                if matches!(build_type, BuildType::Lib)
+                    && metadata.workspace_members.contains(&artifact.package_id)
+                    && BuildType::ProcMacro.matches(&artifact) =>
+            {
+                skipped_proc_macro = true;
+            }
+            // The errors are printed even with `--quiet` to show why the build failed
+            Message::CompilerMessage(msg)
+                if !quiet
+                    || verbose > 1
+                    || matches!(
+                        msg.message.level,
+                        DiagnosticLevel::Error | DiagnosticLevel::Ice
+                    ) =>
+            {
+                if let Some(rendered) = msg.message.rendered {
+                    if json_output(tool, matches) {
+                        eprint!("{rendered}");
+                    } else {
                         print!("{rendered}");
                     }
                 }
@@ -457,15 +2458,120 @@ fn cargo_build(matches: &ArgMatches, metadata: &Metadata) -> Result<Option<Artif
         }
     }
 
-    if target_artifact.is_none() {
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("`cargo build` failed ({status})");
+    }
+
+    if let Some(e) = parse_error {
+        return Err(e.into());
+    }
+
+    let several = several_artifacts(matches);
+    if target_artifacts.len() > 1 && !several {
+        // Like `cargo run`, prefer the `default-run` bin of its package
+        let default_run = |artifact: &Artifact| {
+            artifact.target.kind.iter().any(|kind| kind == "bin")
+                && metadata
+                    .packages
+                    .iter()
+                    .find(|package| package.id == artifact.package_id)
+                    .and_then(|package| package.default_run.as_deref())
+                    == Some(artifact.target.name.as_str())
+        };
+        let mut defaults = target_artifacts
+            .iter()
+            .filter(|artifact| default_run(artifact));
+        if let (Some(artifact), None) = (defaults.next(), defaults.next()) {
+            target_artifacts = vec![artifact.clone()];
+        }
+    }
+
+    if target_artifacts.len() > 1 && !several {
+        let mut candidates = target_artifacts
+            .iter()
+            .map(|artifact| {
+                let kind = artifact
+                    .target
+                    .kind
+                    .first()
+                    .map_or("lib", |kind| kind.as_str());
+                let path = ArtifactFile::from(artifact.clone()).path;
+                match kind {
+                    "bin" | "example" | "test" | "bench" => {
+                        format!("--{kind} {} ({path})", artifact.target.name)
+                    }
+                    _ => format!("--lib ({path})"),
+                }
+            })
+            .collect::<Vec<_>>();
+        candidates.sort();
+
+        bail!(
+            "Can only have one matching artifact but found several, select one with:\n    {}",
+            candidates.join("\n    ")
+        );
+    }
+
+    if target_artifacts.is_empty() {
+        if skipped_proc_macro {
+            bail!("The library is a proc-macro, pass `--proc-macro` to inspect its dylib");
+        }
+
         bail!("Could not determine the wanted artifact");
     }
 
-    Ok(target_artifact)
+    Ok(target_artifacts)
+}
+
+/// Whether `cargo size --format json` prints the sizes as JSON, then nothing else is printed to
+/// stdout
+fn json_output(tool: Tool, matches: &ArgMatches) -> bool {
+    tool == Tool::Size && matches.get_one::<String>("format").map(|s| s.as_str()) == Some("json")
+}
+
+/// The `--message-format` of `cargo build`, the diagnostics are only rendered with colors if the
+/// `--color` flag (or `CARGO_TERM_COLOR`) asks for them, `auto` if where they're printed, stdout
+/// or stderr with `--format json`, is a terminal
+fn message_format(tool: Tool, matches: &ArgMatches) -> &'static str {
+    let color = match matches.get_one::<String>("color") {
+        Some(color) => Some(color.clone()),
+        None => env::var("CARGO_TERM_COLOR").ok(),
+    };
+    let ansi = match color.as_deref() {
+        Some("always") => true,
+        Some("never") => false,
+        _ if json_output(tool, matches) => io::stderr().is_terminal(),
+        _ => io::stdout().is_terminal(),
+    };
+
+    if ansi {
+        "--message-format=json-diagnostic-rendered-ansi"
+    } else {
+        "--message-format=json"
+    }
+}
+
+/// Whether `--workspace` or `--all-targets`, which only some tools have, select several artifacts
+fn several_artifacts(matches: &ArgMatches) -> bool {
+    ["workspace", "all-targets"]
+        .iter()
+        .any(|id| matches.try_get_one::<bool>(id).ok().flatten() == Some(&true))
+}
+
+/// The `CARGO_ENCODED_RUSTFLAGS` that build with `-C instrument-coverage` on top of the `flags`
+/// cargo would otherwise pass to rustc
+fn coverage_rustflags(mut flags: Vec<String>) -> String {
+    const FLAG: &str = "-Cinstrument-coverage";
+
+    if !flags.iter().any(|flag| flag == FLAG) {
+        flags.push(FLAG.to_owned());
+    }
+    flags.join("\x1f")
 }
 
 fn cargo_build_args<'a>(matches: &'a ArgMatches, cargo: &mut Command) -> (BuildType<'a>, u64) {
-    if matches.get_flag("quiet") {
+    if quiet_build(matches) {
         cargo.arg("--quiet");
     }
 
@@ -488,6 +2594,9 @@ fn cargo_build_args<'a>(matches: &'a ArgMatches, cargo: &mut Command) -> (BuildT
     let build_type = if matches.get_flag("lib") {
         cargo.args(["--lib"]);
         BuildType::Lib
+    } else if matches.get_flag("proc-macro") {
+        cargo.args(["--lib"]);
+        BuildType::ProcMacro
     } else if let Some(bin_name) = matches.get_one::<String>("bin") {
         cargo.args(["--bin", bin_name]);
         BuildType::Bin(bin_name)
@@ -500,10 +2609,20 @@ fn cargo_build_args<'a>(matches: &'a ArgMatches, cargo: &mut Command) -> (BuildT
     } else if let Some(bench_name) = matches.get_one::<String>("bench") {
         cargo.args(["--bench", bench_name]);
         BuildType::Bench(bench_name)
+    } else if several_artifacts(matches) {
+        BuildType::All
     } else {
         BuildType::Any
     };
 
+    if several_artifacts(matches) {
+        for flag in ["workspace", "all-targets"] {
+            if matches.get_flag(flag) {
+                cargo.arg(format!("--{flag}"));
+            }
+        }
+    }
+
     if matches.get_flag("release") {
         cargo.arg("--release");
     }
@@ -517,6 +2636,10 @@ fn cargo_build_args<'a>(matches: &'a ArgMatches, cargo: &mut Command) -> (BuildT
         cargo.args(["--manifest-path", manifest_path]);
     }
 
+    if let Some(target_dir) = matches.get_one::<String>("target-dir") {
+        cargo.args(["--target-dir", target_dir]);
+    }
+
     if let Some(features) = matches.get_many::<String>("features") {
         for feature in features {
             cargo.args(["--features", feature]);
@@ -530,7 +2653,7 @@ fn cargo_build_args<'a>(matches: &'a ArgMatches, cargo: &mut Command) -> (BuildT
     }
 
     // NOTE we do *not* use `project.target()` here because Cargo will figure things out on
-    // its own (i.e. it will search and parse .cargo/config, etc.)
+    // its own (i.e. it will search and parse .cargo/config.toml, etc.)
     if let Some(target) = matches.get_one::<String>("target") {
         cargo.args(["--target", target]);
     }
@@ -565,3 +2688,817 @@ fn cargo_build_args<'a>(matches: &'a ArgMatches, cargo: &mut Command) -> (BuildT
 
     (build_type, verbose)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Mutex, MutexGuard};
+
+    use super::*;
+
+    const HOST: &str = "x86_64-unknown-linux-gnu";
+
+    // Held by the tests that set environment variables the others read, as tests run in parallel
+    fn env_lock() -> MutexGuard<'static, ()> {
+        static LOCK: Mutex<()> = Mutex::new(());
+        LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn config(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn matches(tool: Tool, args: &[&str]) -> ArgMatches {
+        let argv = [format!("cargo-{tool}"), tool.name().to_owned()];
+        command(tool, None)
+            .get_matches_from(argv.iter().map(String::as_str).chain(args.iter().copied()))
+    }
+
+    // The arguments `cargo $tool $args` passes to the tool when run on an empty `--file`, whose
+    // path, or name if the tool runs in its directory, is replaced with `$file`
+    fn dry_run(tool: Tool, args: &[&str]) -> Vec<String> {
+        let path = env::temp_dir().join(format!("cargo-binutils-dry-run-{tool}"));
+        fs::write(&path, "").unwrap();
+        let file = path.to_str().unwrap();
+
+        let mut argv = vec!["--dry-run", "--file", file];
+        argv.extend(args);
+        let mut out = vec![];
+        let code = run_with_output(tool, matches(tool, &argv), &mut out).unwrap();
+        assert_eq!(code, 0);
+
+        // e.g. `cd "/tmp" && "/path/to/llvm-objdump" "--wide" "file"`
+        let command = String::from_utf8(out).unwrap();
+        let exe = format!("{}\" ", tool.exe());
+        let args = match command.trim_end().split_once(&exe) {
+            Some((_, args)) => args.trim_start_matches('"').trim_end_matches('"'),
+            None => return vec![],
+        };
+        let name = path.file_name().unwrap().to_str().unwrap();
+        args.split("\" \"")
+            .map(|arg| {
+                if arg == file || arg == name {
+                    "$file"
+                } else {
+                    arg
+                }
+                .to_owned()
+            })
+            .collect()
+    }
+
+    // The metadata of a workspace at `root`, which only has to exist for the tests that look into
+    // its target directory
+    fn workspace_metadata(root: &Utf8Path) -> Metadata {
+        serde_json::from_value(serde_json::json!({
+            "packages": [],
+            "workspace_members": [],
+            "resolve": null,
+            "workspace_root": root,
+            "target_directory": root.join("target"),
+            "version": 1,
+        }))
+        .unwrap()
+    }
+
+    // The `compiler-artifact` message of a library with these `filenames`
+    fn lib_artifact(filenames: &[&Utf8Path]) -> Artifact {
+        serde_json::from_value(serde_json::json!({
+            "package_id": "app 0.1.0 (path+file:///app)",
+            "manifest_path": "/app/Cargo.toml",
+            "target": {
+                "name": "app",
+                "kind": ["lib"],
+                "crate_types": ["lib"],
+                "src_path": "/app/src/lib.rs",
+            },
+            "profile": {
+                "opt_level": "0",
+                "debuginfo": 2,
+                "debug_assertions": true,
+                "overflow_checks": true,
+                "test": false,
+            },
+            "features": [],
+            "filenames": filenames,
+            "executable": null,
+            "fresh": false,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn build_fingerprint_changes_with_the_build_flags() {
+        let fingerprint = |tool, args: &[&str]| build_fingerprint(tool, &matches(tool, args));
+
+        let plain = fingerprint(Tool::Size, &[]);
+        assert_eq!(plain, fingerprint(Tool::Size, &["--if-changed", "-v"]));
+        assert_ne!(plain, fingerprint(Tool::Size, &["--release"]));
+        assert_ne!(plain, fingerprint(Tool::Size, &["--features", "a"]));
+        assert_ne!(
+            fingerprint(Tool::Size, &["--features", "a"]),
+            fingerprint(Tool::Size, &["--features", "b"])
+        );
+        assert_ne!(plain, fingerprint(Tool::Cov, &[]));
+    }
+
+    #[test]
+    fn dry_run_without_the_tool_installed() {
+        // Some of these likely aren't installed
+        for &tool in Tool::all().iter().filter(|tool| !tool.can_build()) {
+            let mut out = vec![];
+            let code = run_with_output(tool, matches(tool, &["--dry-run"]), &mut out).unwrap();
+
+            assert_eq!(code, 0);
+            let exe = tool.exe();
+            assert!(String::from_utf8(out).unwrap().contains(&exe), "{tool}");
+        }
+    }
+
+    #[test]
+    fn lone_target_without_tables() {
+        assert_eq!(lone_target(&config(""), HOST), None);
+        assert_eq!(lone_target(&config("[build]\njobs = 1"), HOST), None);
+    }
+
+    #[test]
+    fn lone_target_with_one_table() {
+        let cross = config("[target.thumbv7em-none-eabihf]\nrunner = 'probe-rs run'");
+        assert_eq!(lone_target(&cross, HOST), Some("thumbv7em-none-eabihf"));
+
+        // The host doesn't need to be inferred
+        let host = config("[target.x86_64-unknown-linux-gnu]\nlinker = 'clang'");
+        assert_eq!(lone_target(&host, HOST), None);
+
+        // Nor do `cfg(..)` tables name a target
+        let cfg = config(
+            "[target.'cfg(all(target_arch = \"arm\", target_os = \"none\"))']\nrunner = 'x'\n\
+             [target.thumbv6m-none-eabi]\nrunner = 'y'",
+        );
+        assert_eq!(lone_target(&cfg, HOST), Some("thumbv6m-none-eabi"));
+    }
+
+    #[test]
+    fn lone_target_with_several_tables() {
+        let several = config(
+            "[target.thumbv6m-none-eabi]\nrunner = 'x'\n[target.thumbv7m-none-eabi]\nrunner = 'y'",
+        );
+        assert_eq!(lone_target(&several, HOST), None);
+    }
+
+    #[test]
+    fn coverage_rustflags_keep_the_other_flags() {
+        assert_eq!(coverage_rustflags(vec![]), "-Cinstrument-coverage");
+        assert_eq!(
+            coverage_rustflags(vec!["-C".to_owned(), "link-arg=-T link.x".to_owned()]),
+            "-C\x1flink-arg=-T link.x\x1f-Cinstrument-coverage"
+        );
+        assert_eq!(
+            coverage_rustflags(vec!["-Cinstrument-coverage".to_owned()]),
+            "-Cinstrument-coverage"
+        );
+    }
+
+    #[test]
+    fn library_file_prefers_the_rlib() {
+        let dir = Utf8Path::new("/app/target/thumbv7em-none-eabihf/debug");
+        let rlib = dir.join("libapp.rlib");
+        let rmeta = dir.join("libapp.rmeta");
+        let staticlib = dir.join("libapp.a");
+
+        for filenames in [[&rlib, &rmeta], [&rmeta, &rlib]] {
+            let filenames = filenames.map(|file| file.as_path());
+            assert_eq!(library_file(&lib_artifact(&filenames)), &rlib);
+        }
+        for filenames in [[&rmeta, &staticlib], [&staticlib, &rmeta]] {
+            let filenames = filenames.map(|file| file.as_path());
+            assert_eq!(library_file(&lib_artifact(&filenames)), &staticlib);
+        }
+        assert_eq!(library_file(&lib_artifact(&[&rmeta])), &rmeta);
+
+        let file = ArtifactFile::from(lib_artifact(&[&rmeta, &rlib]));
+        assert_eq!((file.path, file.executable), (rlib, false));
+    }
+
+    #[test]
+    fn context_from_the_artifact_path() {
+        let metadata = workspace_metadata(Utf8Path::new("/app"));
+        let target_dir = &metadata.target_directory;
+
+        for filenames in [
+            ["libapp.rlib", "libapp.rmeta"],
+            ["libapp.rmeta", "libapp.rlib"],
+        ] {
+            let dir = target_dir.join("thumbv7em-none-eabihf/release");
+            let filenames = filenames.map(|file| dir.join(file));
+            let artifact = lib_artifact(&filenames.iter().map(|f| f.as_path()).collect::<Vec<_>>());
+
+            let ctxt = Context::from_artifact(&metadata, library_file(&artifact)).unwrap();
+            assert_eq!(ctxt.target, "thumbv7em-none-eabihf");
+            assert_eq!(
+                (ctxt.arch.as_str(), ctxt.endian.as_str()),
+                ("arm", "little")
+            );
+        }
+
+        let host = rustc_version::version_meta().unwrap().host;
+        let ctxt = Context::from_artifact(&metadata, &target_dir.join("debug/app")).unwrap();
+        assert_eq!(ctxt.target, host);
+
+        assert!(Context::from_artifact(&metadata, Utf8Path::new("/elsewhere/app")).is_err());
+    }
+
+    #[test]
+    fn objdump_wide() {
+        assert_eq!(
+            dry_run(
+                Tool::Objdump,
+                &["--target", "thumbv7em-none-eabihf", "--wide"]
+            ),
+            ["--triple", "thumbv7em-none-eabihf", "--wide", "$file"]
+        );
+    }
+
+    #[test]
+    fn glob_matches() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("app-*", "app-0123abcd"));
+        assert!(glob_match("*.profraw", "default_1234.profraw"));
+        assert!(glob_match("a?c", "abc"));
+        assert!(glob_match("*a*b*", "xxaxxbxx"));
+        assert!(glob_match("a*b", "abab"));
+
+        assert!(!glob_match("a?c", "ac"));
+        assert!(!glob_match("app-*", "lib-app"));
+        assert!(!glob_match("*.profraw", "default.profdata"));
+        assert!(!glob_match("", "a"));
+    }
+
+    #[test]
+    fn header_json_schema_version() {
+        let file = env::temp_dir().join("cargo-binutils-header-json.elf");
+        fs::write(&file, elf::tests::elf64()).unwrap();
+
+        let mut out = vec![];
+        assert_eq!(print_header_json(&file, &mut out).unwrap(), 0);
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["schema_version"], JSON_SCHEMA_VERSION);
+        assert_eq!(json["machine"], "X86_64");
+
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn config_files() {
+        let root = env::temp_dir().join("cargo-binutils-config-files");
+        let _ = fs::remove_dir_all(&root);
+        let nested = root.join("crates/app/src");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(root.join(".cargo")).unwrap();
+
+        assert_eq!(search_config(&nested), None);
+
+        fs::write(
+            root.join(".cargo/config"),
+            "build.target = 'thumbv6m-none-eabi'",
+        )
+        .unwrap();
+        assert_eq!(search_config(&nested), Some(root.join(".cargo/config")));
+
+        // The modern name wins over the deprecated one
+        let config = root.join(".cargo/config.toml");
+        fs::write(&config, "[build]\ntarget = 'thumbv7em-none-eabihf'").unwrap();
+        assert_eq!(search_config(&nested), Some(config.clone()));
+        assert_eq!(search_config(&root), Some(config));
+
+        let metadata = workspace_metadata(Utf8Path::from_path(&root).unwrap());
+        assert_eq!(
+            config_file_target(&metadata).unwrap().as_deref(),
+            Some("thumbv7em-none-eabihf")
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn cargo_build_target_precedence() {
+        let root = env::temp_dir().join("cargo-binutils-build-target");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(".cargo")).unwrap();
+        fs::write(
+            root.join(".cargo/config.toml"),
+            "build.target = 'thumbv7em-none-eabihf'",
+        )
+        .unwrap();
+        let metadata = workspace_metadata(Utf8Path::from_path(&root).unwrap());
+        let target = |args: &[&str]| build_target(&matches(Tool::Size, args), &metadata).unwrap();
+        let ctxt_target = |flag| Context::from_flag(&metadata, flag).unwrap().target;
+
+        let _lock = env_lock();
+        env::set_var("CARGO_BUILD_TARGET", "thumbv6m-none-eabi");
+        let env_target = target(&[]);
+        let flag_target = target(&["--target", "thumbv8m.main-none-eabi"]);
+        let config_arg_target =
+            target(&["--config", "build.target='riscv32imac-unknown-none-elf'"]);
+        let env_ctxt_target = ctxt_target(None);
+        let flag_ctxt_target = ctxt_target(Some("thumbv8m.main-none-eabi"));
+        env::remove_var("CARGO_BUILD_TARGET");
+
+        // The variable takes precedence over the config files but not over the flags
+        assert_eq!(env_target.as_deref(), Some("thumbv6m-none-eabi"));
+        assert_eq!(flag_target.as_deref(), Some("thumbv8m.main-none-eabi"));
+        assert_eq!(
+            config_arg_target.as_deref(),
+            Some("riscv32imac-unknown-none-elf")
+        );
+        assert_eq!(env_ctxt_target, "thumbv6m-none-eabi");
+        assert_eq!(flag_ctxt_target, "thumbv8m.main-none-eabi");
+
+        assert_eq!(target(&[]).as_deref(), Some("thumbv7em-none-eabihf"));
+        assert_eq!(ctxt_target(None), "thumbv7em-none-eabihf");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn config_arg_targets() {
+        let target = |args: &[&str]| config_arg_target(&matches(Tool::Objdump, args)).unwrap();
+
+        assert_eq!(target(&[]), None);
+        // Per target settings don't select a target
+        assert_eq!(
+            target(&[
+                "--config",
+                "target.thumbv7m-none-eabi.runner='probe-rs run'"
+            ]),
+            None
+        );
+        // Let cargo report the invalid ones
+        assert_eq!(target(&["--config", "build.target ="]), None);
+
+        let file = env::temp_dir().join("cargo-binutils-config-arg.toml");
+        fs::write(&file, "[build]\ntarget = 'thumbv6m-none-eabi'").unwrap();
+        let file = file.to_str().unwrap();
+        assert_eq!(
+            target(&["--config", file]).as_deref(),
+            Some("thumbv6m-none-eabi")
+        );
+
+        // The last one wins
+        assert_eq!(
+            target(&[
+                "--config",
+                "build.target='thumbv7em-none-eabihf'",
+                "--config",
+                file,
+                "--config",
+                "build.jobs=1",
+            ])
+            .as_deref(),
+            Some("thumbv6m-none-eabi")
+        );
+        assert_eq!(
+            target(&[
+                "--config",
+                file,
+                "--config",
+                "build.target=['thumbv7em-none-eabihf']"
+            ])
+            .as_deref(),
+            Some("thumbv7em-none-eabihf")
+        );
+
+        fs::remove_file(file).unwrap();
+
+        // Nor do they get in the way of the target objdump is given
+        let args = dry_run(
+            Tool::Objdump,
+            &[
+                "--config",
+                "target.thumbv7m-none-eabi.runner='probe-rs run'",
+                "--target",
+                "thumbv7m-none-eabi",
+            ],
+        );
+        assert_eq!(args, ["--triple", "thumbv7m-none-eabi", "$file"]);
+    }
+
+    #[test]
+    fn objdump_arm_triple() {
+        assert_eq!(
+            dry_run(Tool::Objdump, &["--target", "armv7r-none-eabihf"]),
+            ["--triple", "armv7r-none-eabihf", "$file"]
+        );
+    }
+
+    #[test]
+    fn objdump_custom_target_triple() {
+        let dir = env::temp_dir().join("cargo-binutils-custom-target");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let spec = dir.join("my-board.json");
+        fs::write(
+            &spec,
+            r#"{
+                "arch": "arm",
+                "data-layout": "e-m:e-p:32:32-Fi8-i64:64-v128:64:128-a:0:32-n32-S64",
+                "llvm-target": "thumbv7em-none-eabi",
+                "target-pointer-width": "32"
+            }"#,
+        )
+        .unwrap();
+
+        let context = Context::from_target_name(spec.to_str().unwrap()).unwrap();
+        assert_eq!(context.arch, "arm");
+        assert_eq!(context.endian, "little");
+        assert_eq!(
+            dry_run(Tool::Objdump, &["--target", spec.to_str().unwrap()]),
+            ["--triple", "thumbv7em-none-eabi", "$file"]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn context_from_the_artifact_of_a_custom_profile() {
+        let root = env::temp_dir().join("cargo-binutils-custom-profile");
+        let _ = fs::remove_dir_all(&root);
+        let root = Utf8PathBuf::from_path_buf(root).unwrap();
+        let metadata = workspace_metadata(&root);
+        let target_dir = &metadata.target_directory;
+        fs::create_dir_all(target_dir.join("release-lto/.fingerprint")).unwrap();
+        fs::create_dir_all(target_dir.join("thumbv7m-none-eabi/release-lto/.fingerprint")).unwrap();
+
+        let host = rustc_version::version_meta().unwrap().host;
+        let ctxt = Context::from_artifact(&metadata, &target_dir.join("release-lto/app")).unwrap();
+        assert_eq!(ctxt.target, host);
+
+        let app = target_dir.join("thumbv7m-none-eabi/release-lto/app");
+        let ctxt = Context::from_artifact(&metadata, &app).unwrap();
+        assert_eq!(ctxt.target, "thumbv7m-none-eabi");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn split_flags_like_a_shell() {
+        assert_eq!(
+            split_flags("  -C  opt-level=3\t-g ").unwrap(),
+            ["-C", "opt-level=3", "-g"]
+        );
+        assert_eq!(
+            split_flags(r#"-C 'link-arg=-T link.x' "a \"b\"" c\ d ''"#).unwrap(),
+            ["-C", "link-arg=-T link.x", "a \"b\"", "c d", ""]
+        );
+        assert_eq!(split_flags(r"'a\b'").unwrap(), [r"a\b"]);
+        assert!(split_flags("").unwrap().is_empty());
+
+        assert_eq!(
+            split_flags("'a").unwrap_err().to_string(),
+            "unterminated `'` quote"
+        );
+        assert_eq!(split_flags("a\\").unwrap_err().to_string(), "trailing `\\`");
+    }
+
+    #[test]
+    fn memory_x_of_the_package() {
+        let root = env::temp_dir().join("cargo-binutils-memory-x");
+        let _ = fs::remove_dir_all(&root);
+        let root = Utf8PathBuf::from_path_buf(root).unwrap();
+        let metadata = workspace_metadata(&root);
+        let firmware = root.join("firmware");
+        fs::create_dir_all(firmware.join("src")).unwrap();
+        fs::write(root.join("memory.x"), "").unwrap();
+        fs::write(firmware.join("memory.x"), "").unwrap();
+        let artifact = |manifest_dir: Option<&Utf8Path>| ArtifactFile {
+            path: metadata.target_directory.join("debug/firmware"),
+            executable: true,
+            manifest_dir: manifest_dir.map(|dir| dir.to_owned()),
+        };
+
+        let memory_x = |artifact| memory_x(Some(&metadata), Some(&artifact)).unwrap();
+        assert_eq!(
+            memory_x(artifact(Some(&firmware))),
+            Some(firmware.join("memory.x").into())
+        );
+        // Up from the package, e.g. one shared by the packages of the workspace
+        fs::remove_file(firmware.join("memory.x")).unwrap();
+        assert_eq!(
+            memory_x(artifact(Some(&firmware))),
+            Some(root.join("memory.x").into())
+        );
+        // `--file`
+        assert_eq!(memory_x(artifact(None)), Some(root.join("memory.x").into()));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn file_skips_the_build() {
+        // A crate that doesn't build
+        let root = env::temp_dir().join("cargo-binutils-file-skips-the-build");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[workspace]\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {").unwrap();
+        let manifest = root.join("Cargo.toml");
+
+        assert_eq!(
+            dry_run(
+                Tool::Nm,
+                &["--manifest-path", manifest.to_str().unwrap(), "--", "-S"]
+            ),
+            ["$file", "-S"]
+        );
+        assert!(command(Tool::Nm, None)
+            .try_get_matches_from(["cargo-nm", "nm", "--file", "app.elf", "--bin", "app"])
+            .is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn readobj_output_style() {
+        let style = |args: &[&str]| {
+            let mut argv = vec!["--"];
+            argv.extend(args);
+            dry_run(Tool::Readobj, &argv)
+                .into_iter()
+                .filter(|arg| arg.contains("elf-output-style") || arg == "LLVM")
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(style(&[]), ["--elf-output-style=GNU"]);
+        assert_eq!(
+            style(&["--elf-output-style=JSON"]),
+            ["--elf-output-style=JSON"]
+        );
+        assert_eq!(
+            style(&["--elf-output-style", "LLVM"]),
+            ["--elf-output-style", "LLVM"]
+        );
+    }
+
+    #[test]
+    fn message_formats() {
+        let ansi = "--message-format=json-diagnostic-rendered-ansi";
+        let format = |args: &[&str]| message_format(Tool::Size, &matches(Tool::Size, args));
+
+        let _lock = env_lock();
+        env::set_var("CARGO_TERM_COLOR", "always");
+        assert_eq!(format(&[]), ansi);
+        // The flag wins over the environment
+        assert_eq!(format(&["--color", "never"]), "--message-format=json");
+        env::set_var("CARGO_TERM_COLOR", "never");
+        assert_eq!(format(&["--color", "always"]), ansi);
+        assert_eq!(format(&["--format", "json"]), "--message-format=json");
+        env::remove_var("CARGO_TERM_COLOR");
+    }
+
+    #[test]
+    fn streams_large_outputs() {
+        // Counts the bytes written to it rather than keeping them
+        #[derive(Default)]
+        struct Counter {
+            total: usize,
+            largest_write: usize,
+        }
+
+        impl Write for Counter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.total += buf.len();
+                self.largest_write = self.largest_write.max(buf.len());
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let size = 64 << 20;
+        let mut out = Counter::default();
+        let output = BufReader::new(io::Read::take(io::repeat(b'a'), size as u64));
+        stream(output, false, &mut out).unwrap();
+        assert_eq!(out.total, size);
+        assert!(out.largest_write <= 64 << 10, "{}", out.largest_write);
+
+        let line = b"  8000100: bl <_ZN3foo3barE+0x8>\n";
+        let lines = 100_000;
+        let mut out = Counter::default();
+        stream(&line.repeat(lines)[..], true, &mut out).unwrap();
+        assert_eq!(out.total, lines * "  8000100: bl <foo::bar+0x8>\n".len());
+        assert!(out.largest_write < line.len(), "{}", out.largest_write);
+    }
+
+    #[test]
+    fn cov_command_line() {
+        let profdata = env::temp_dir().join("default.profdata");
+        let _ = fs::remove_file(&profdata);
+        assert_eq!(dry_run(Tool::Cov, &["--", "report"]), ["report", "$file"]);
+
+        fs::write(&profdata, "").unwrap();
+        let instr_profile = format!("--instr-profile={}", profdata.display());
+        assert_eq!(
+            dry_run(Tool::Cov, &["--", "report", "--summary-only"]),
+            ["report", "$file", &instr_profile, "--summary-only"]
+        );
+        // The user's profile wins
+        assert_eq!(
+            dry_run(Tool::Cov, &["--", "show", "--instr-profile=app.profdata"]),
+            ["show", "$file", "--instr-profile=app.profdata"]
+        );
+        fs::remove_file(&profdata).unwrap();
+    }
+
+    #[test]
+    fn profdata_merge_dir() {
+        let dir = env::temp_dir().join("cargo-binutils-merge-dir");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested.profraw")).unwrap();
+        for file in ["b.profraw", "a.profraw", "default.profdata"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        let files = profraw_files(&dir).unwrap();
+        assert_eq!(files, [dir.join("a.profraw"), dir.join("b.profraw")]);
+
+        let merge_dir = dir.to_str().unwrap();
+        let mut out = vec![];
+        let matches = matches(Tool::Profdata, &["--dry-run", "--merge-dir", merge_dir]);
+        assert_eq!(
+            run_with_output(Tool::Profdata, matches, &mut out).unwrap(),
+            0
+        );
+        let command = String::from_utf8(out).unwrap();
+        let args = files
+            .iter()
+            .map(|file| format!("{file:?}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert!(
+            command.trim_end().ends_with(&format!(
+                "\"merge\" \"-sparse\" {args} \"-o\" \"default.profdata\""
+            )),
+            "{command}"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(profraw_files(&dir).is_err());
+    }
+
+    #[test]
+    fn objdump_loongarch() {
+        let ctxt = Context::from_target_name("loongarch64-unknown-linux-gnu").unwrap();
+        assert_eq!(
+            llvm::arch_name(&ctxt.arch, &ctxt.endian, &ctxt.target),
+            "loongarch64"
+        );
+        assert_eq!(
+            dry_run(
+                Tool::Objdump,
+                &["--target", "loongarch64-unknown-linux-gnu"]
+            ),
+            ["--triple", "loongarch64-unknown-linux-gnu", "$file"]
+        );
+    }
+
+    #[test]
+    fn objdump_bpf() {
+        for (target, arch_name) in [
+            ("bpfel-unknown-none", "bpfel"),
+            ("bpfeb-unknown-none", "bpfeb"),
+        ] {
+            let ctxt = Context::from_target_name(target).unwrap();
+            assert_eq!(
+                llvm::arch_name(&ctxt.arch, &ctxt.endian, &ctxt.target),
+                arch_name
+            );
+            assert_eq!(
+                dry_run(Tool::Objdump, &["--target", target]),
+                ["--triple", target, "$file"]
+            );
+        }
+    }
+
+    #[test]
+    fn objdump_avr() {
+        // The AVR target of older toolchains was renamed to `avr-none`
+        let target = ["avr-none", "avr-unknown-gnu-atmega328"]
+            .into_iter()
+            .find(|target| Context::from_target_name(target).is_ok())
+            .unwrap();
+        assert_eq!(
+            dry_run(Tool::Objdump, &["--target", target]),
+            ["--triple", target, "$file"]
+        );
+    }
+
+    #[test]
+    fn objdump_m68k() {
+        assert_eq!(
+            dry_run(Tool::Objdump, &["--target", "m68k-unknown-linux-gnu"]),
+            ["--triple", "m68k-unknown-linux-gnu", "$file"]
+        );
+    }
+
+    #[test]
+    fn objdump_prefers_the_triple() {
+        for (target, triple) in [
+            ("thumbv6m-none-eabi", "thumbv6m-none-eabi"),
+            (
+                "armv7-unknown-linux-gnueabihf",
+                "armv7-unknown-linux-gnueabihf",
+            ),
+            ("riscv32imac-unknown-none-elf", "riscv32-unknown-none-elf"),
+            ("riscv64gc-unknown-none-elf", "riscv64-unknown-none-elf"),
+            ("x86_64-unknown-linux-gnu", "x86_64-unknown-linux-gnu"),
+        ] {
+            assert_eq!(
+                dry_run(Tool::Objdump, &["--target", target]),
+                ["--triple", triple, "$file"]
+            );
+        }
+
+        // Unless overridden, by the flag or by the user's own flags
+        assert_eq!(
+            dry_run(
+                Tool::Objdump,
+                &[
+                    "--target",
+                    "riscv32imac-unknown-none-elf",
+                    "--arch-name",
+                    "riscv32"
+                ]
+            ),
+            ["--arch-name=riscv32", "$file"]
+        );
+        assert_eq!(
+            dry_run(
+                Tool::Objdump,
+                &[
+                    "--target",
+                    "x86_64-unknown-linux-gnu",
+                    "--",
+                    "--triple=i686"
+                ]
+            ),
+            ["$file", "--triple=i686"]
+        );
+    }
+
+    #[test]
+    fn strip_in_place() {
+        assert_eq!(
+            dry_run(Tool::Strip, &["--in-place"]),
+            ["$file", "-o", "$file"]
+        );
+
+        let rlib = env::temp_dir().join("cargo-binutils-strip-in-place.rlib");
+        fs::write(&rlib, "").unwrap();
+        let args = ["--dry-run", "--in-place", "--file", rlib.to_str().unwrap()];
+        let e = run_with_output(Tool::Strip, matches(Tool::Strip, &args), &mut vec![]).unwrap_err();
+        assert!(e.to_string().starts_with("Refusing to strip"), "{e}");
+        fs::remove_file(&rlib).unwrap();
+    }
+
+    #[test]
+    fn split_debug_commands() {
+        let path = env::temp_dir().join("cargo-binutils-split-debug");
+        fs::write(&path, "").unwrap();
+        let file = path.to_str().unwrap();
+        let commands = |args: &[&str]| {
+            let mut argv = vec!["--dry-run", "--file", file];
+            argv.extend(args);
+            let mut out = vec![];
+            let code = run_with_output(Tool::Objcopy, matches(Tool::Objcopy, &argv), &mut out);
+            assert_eq!(code.unwrap(), 0);
+            String::from_utf8(out).unwrap()
+        };
+        let exe = format!("{}\"", Tool::Objcopy.exe());
+
+        let out = commands(&["--split-debug"]);
+        let expected = [
+            format!("{exe} \"--only-keep-debug\" \"{file}\" \"{file}.debug\""),
+            format!("{exe} \"--strip-debug\" \"{file}\""),
+            format!("{exe} \"--add-gnu-debuglink={file}.debug\" \"{file}\""),
+        ];
+        assert_eq!(out.lines().count(), expected.len(), "{out}");
+        for (command, expected) in out.lines().zip(&expected) {
+            assert!(command.ends_with(expected), "{command}");
+        }
+
+        let out = commands(&["--split-debug", "app.debug", "--compress-debug", "zlib"]);
+        let first = out.lines().next().unwrap();
+        let expected = format!(
+            "{exe} \"--only-keep-debug\" \"--compress-debug-sections=zlib\" \"{file}\" \"app.debug\""
+        );
+        assert!(first.ends_with(&expected), "{first}");
+        assert!(out.contains("\"--add-gnu-debuglink=app.debug\""), "{out}");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,4 +1,4 @@
-use rustc_cfg::Cfg;
+use std::borrow::Cow;
 
 // Here we map Rust arches to LLVM arches
 //
@@ -53,13 +53,10 @@ use rustc_cfg::Cfg;
 // - wasm64
 // - x86
 // - x86-64
-pub fn arch_name<'a>(cfg: &'a Cfg, target: &'a str) -> &'a str {
+pub fn arch_name<'a>(arch: &'a str, endian: &str, target: &str) -> &'a str {
     const BIG: &str = "big";
     const LITTLE: &str = "little";
 
-    let endian = &*cfg.target_endian;
-    let arch = &*cfg.target_arch;
-
     if target.starts_with("thumb") {
         // no way to tell from `--print cfg` that the target is thumb only so we
         // completely rely on the target name here
@@ -73,6 +70,8 @@ pub fn arch_name<'a>(cfg: &'a Cfg, target: &'a str) -> &'a str {
             // non standard endianness
             ("aarch64", BIG) => "aarch64_be",
             ("arm", BIG) => "armeb",
+            ("bpf", BIG) => "bpfeb",
+            ("bpf", LITTLE) => "bpfel",
             ("mips", LITTLE) => "mipsel",
             ("mips64", LITTLE) => "mips64el",
             ("powerpc64", LITTLE) => "ppc64le",
@@ -85,8 +84,380 @@ pub fn arch_name<'a>(cfg: &'a Cfg, target: &'a str) -> &'a str {
             ("s390x", _) => "systemz",
             ("x86_64", _) => "x86-64",
 
+            // arches Rust gained after 1.28, whose names LLVM shares
+            ("loongarch32", _) => "loongarch32",
+            ("loongarch64", _) => "loongarch64",
+
             // all the other names match as of 1.28
             _ => arch,
         }
     }
 }
+
+/// The LLVM triple of the Rust target `target`. Most targets are named after their LLVM triple but
+/// some name things LLVM doesn't know, e.g. the ISA extensions of `riscv32imac-unknown-none-elf` or
+/// the float ABI of `aarch64-unknown-none-softfloat`.
+pub fn triple(target: &str) -> Cow<'_, str> {
+    // Targets whose LLVM triple has nothing to do with their name
+    match target {
+        "armv6k-nintendo-3ds" => return "armv6k-none-eabihf".into(),
+        "armv7-sony-vita-newlibeabihf" => return "thumbv7a-sony-vita-eabihf".into(),
+        _ => {}
+    }
+
+    let mut parts = target.split('-').collect::<Vec<_>>();
+    let original = parts.clone();
+
+    for riscv in ["riscv32", "riscv64"] {
+        if parts[0].starts_with(riscv) {
+            parts[0] = riscv;
+        }
+    }
+
+    for part in &mut parts[1..] {
+        *part = match *part {
+            // Rust's UEFI targets are the Windows ones of LLVM
+            "uefi" => "windows",
+            // e.g. `aarch64-apple-ios-sim`
+            "sim" => "simulator",
+            // e.g. `newlibeabihf`, the C library isn't part of LLVM's environment
+            part => match part.strip_prefix("newlib") {
+                Some(env) if !env.is_empty() => env,
+                _ => part,
+            },
+        };
+    }
+
+    // `-softfloat` is the float ABI of Rust's targets, LLVM picks it with a target feature
+    if parts.len() > 2 && parts.last() == Some(&"softfloat") {
+        parts.pop();
+    }
+
+    if parts == original {
+        target.into()
+    } else {
+        parts.join("-").into()
+    }
+}
+
+/// The `e_machine` of the ELF files of the LLVM arch `arch_name` (see `arch_name`)
+pub fn elf_machine(arch_name: &str) -> Option<u16> {
+    Some(match arch_name {
+        "x86" => 3,
+        "x86-64" => 62,
+        "arm" | "armeb" | "thumb" | "thumbeb" => 40,
+        "aarch64" | "aarch64_be" => 183,
+        "mips" | "mipsel" | "mips64" | "mips64el" => 8,
+        "ppc32" => 20,
+        "ppc64" | "ppc64le" => 21,
+        "riscv32" | "riscv64" => 243,
+        "sparc" | "sparcel" => 2,
+        "sparcv9" => 43,
+        "systemz" => 22,
+        "msp430" => 105,
+        "avr" => 83,
+        "hexagon" => 164,
+        "bpf" | "bpfeb" | "bpfel" => 247,
+        "loongarch32" | "loongarch64" => 258,
+        "m68k" => 4,
+        _ => return None,
+    })
+}
+
+/// The `--output-target` of `objcopy` for ELF files of the LLVM arch `arch_name` with the given
+/// class and endianness, e.g. `elf32-littlearm`
+pub fn elf_output_target(arch_name: &str, is64: bool, big_endian: bool) -> Option<String> {
+    let bits = if is64 { 64 } else { 32 };
+    let endian = if big_endian { "big" } else { "little" };
+
+    Some(match arch_name {
+        "x86" => "elf32-i386".to_owned(),
+        "x86-64" => format!("elf{bits}-x86-64"),
+        "arm" | "armeb" | "thumb" | "thumbeb" => format!("elf32-{endian}arm"),
+        "aarch64" | "aarch64_be" => format!("elf64-{endian}aarch64"),
+        "mips" | "mipsel" | "mips64" | "mips64el" => format!("elf{bits}-trad{endian}mips"),
+        "ppc32" | "ppc64" | "ppc64le" if big_endian => format!("elf{bits}-powerpc"),
+        "ppc32" | "ppc64" | "ppc64le" => format!("elf{bits}-powerpcle"),
+        "riscv32" | "riscv64" => format!("elf{bits}-littleriscv"),
+        "sparc" => "elf32-sparc".to_owned(),
+        "sparcv9" => "elf64-sparc".to_owned(),
+        "systemz" => "elf64-s390".to_owned(),
+        "hexagon" => "elf32-hexagon".to_owned(),
+        "loongarch32" | "loongarch64" => format!("elf{bits}-loongarch"),
+        "m68k" => "elf32-m68k".to_owned(),
+        _ => return None,
+    })
+}
+
+/// The `--mcpu` and `--mattr` of the `llvm-objdump` disassembler that correspond to the last
+/// `-C target-cpu` and all the `-C target-feature`s of `rustflags`
+pub fn cpu_flags<S: AsRef<str>>(rustflags: &[S]) -> (Option<String>, Option<String>) {
+    let mut cpu = None;
+    let mut features = vec![];
+
+    let mut flags = rustflags.iter().map(AsRef::as_ref);
+    while let Some(flag) = flags.next() {
+        let codegen = match flag {
+            "-C" | "--codegen" => match flags.next() {
+                Some(option) => option,
+                None => break,
+            },
+            _ => match flag
+                .strip_prefix("-C")
+                .or_else(|| flag.strip_prefix("--codegen="))
+            {
+                Some(option) => option,
+                None => continue,
+            },
+        };
+
+        if let Some(name) = codegen.strip_prefix("target-cpu=") {
+            // LLVM's disassembler has no notion of the host CPU
+            cpu = Some(name).filter(|name| *name != "native");
+        } else if let Some(list) = codegen.strip_prefix("target-feature=") {
+            features.extend(
+                list.split(',')
+                    // `crt-static` is a Rust feature, not an LLVM one
+                    .filter(|feature| {
+                        !feature.is_empty()
+                            && feature.trim_start_matches(['+', '-']) != "crt-static"
+                    }),
+            );
+        }
+    }
+
+    let mattr = Some(features.join(",")).filter(|mattr| !mattr.is_empty());
+    (cpu.map(str::to_owned), mattr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triples() {
+        for (target, llvm_triple) in [
+            // Named after their LLVM triple
+            ("thumbv7em-none-eabihf", "thumbv7em-none-eabihf"),
+            ("thumbv8m.main-none-eabi", "thumbv8m.main-none-eabi"),
+            (
+                "armv7-unknown-linux-gnueabihf",
+                "armv7-unknown-linux-gnueabihf",
+            ),
+            ("armebv7r-none-eabi", "armebv7r-none-eabi"),
+            ("aarch64-unknown-linux-gnu", "aarch64-unknown-linux-gnu"),
+            ("i686-pc-windows-msvc", "i686-pc-windows-msvc"),
+            ("x86_64-unknown-linux-gnu", "x86_64-unknown-linux-gnu"),
+            // LLVM knows the x32 ABI
+            ("x86_64-unknown-linux-gnux32", "x86_64-unknown-linux-gnux32"),
+            ("mipsel-sony-psp", "mipsel-sony-psp"),
+            (
+                "powerpc64le-unknown-linux-gnu",
+                "powerpc64le-unknown-linux-gnu",
+            ),
+            ("sparc-unknown-none-elf", "sparc-unknown-none-elf"),
+            ("s390x-unknown-linux-gnu", "s390x-unknown-linux-gnu"),
+            (
+                "loongarch64-unknown-linux-gnu",
+                "loongarch64-unknown-linux-gnu",
+            ),
+            ("bpfel-unknown-none", "bpfel-unknown-none"),
+            ("avr-unknown-gnu-atmega328", "avr-unknown-gnu-atmega328"),
+            ("m68k-unknown-linux-gnu", "m68k-unknown-linux-gnu"),
+            ("msp430-none-elf", "msp430-none-elf"),
+            ("wasm32-unknown-unknown", "wasm32-unknown-unknown"),
+            // ISA extensions
+            ("riscv32imac-unknown-none-elf", "riscv32-unknown-none-elf"),
+            ("riscv32imc-esp-espidf", "riscv32-esp-espidf"),
+            ("riscv64gc-unknown-linux-gnu", "riscv64-unknown-linux-gnu"),
+            ("riscv64-linux-android", "riscv64-linux-android"),
+            // Float ABI
+            ("aarch64-unknown-none-softfloat", "aarch64-unknown-none"),
+            (
+                "aarch64_be-unknown-none-softfloat",
+                "aarch64_be-unknown-none",
+            ),
+            (
+                "loongarch64-unknown-none-softfloat",
+                "loongarch64-unknown-none",
+            ),
+            ("s390x-unknown-none-softfloat", "s390x-unknown-none"),
+            // C library
+            ("armv7-sony-vita-newlibeabihf", "thumbv7a-sony-vita-eabihf"),
+            ("armv6k-nintendo-3ds", "armv6k-none-eabihf"),
+            // OS and environment
+            ("x86_64-unknown-uefi", "x86_64-unknown-windows"),
+            ("aarch64-apple-ios-sim", "aarch64-apple-ios-simulator"),
+        ] {
+            assert_eq!(triple(target), llvm_triple, "{target}");
+        }
+    }
+
+    #[test]
+    fn m68k() {
+        assert_eq!(arch_name("m68k", "big", "m68k-unknown-linux-gnu"), "m68k");
+        assert_eq!(elf_machine("m68k"), Some(4));
+        assert_eq!(
+            elf_output_target("m68k", false, true).as_deref(),
+            Some("elf32-m68k")
+        );
+    }
+
+    #[test]
+    fn elf_machines() {
+        for (arch_name, machine) in [
+            ("x86", 3),
+            ("x86-64", 62),
+            ("thumb", 40),
+            ("armeb", 40),
+            ("aarch64_be", 183),
+            ("mips64el", 8),
+            ("ppc32", 20),
+            ("ppc64le", 21),
+            ("riscv64", 243),
+            ("sparc", 2),
+            ("sparcv9", 43),
+            ("systemz", 22),
+            ("msp430", 105),
+            ("avr", 83),
+            ("hexagon", 164),
+            ("bpfel", 247),
+            ("loongarch64", 258),
+            ("m68k", 4),
+        ] {
+            assert_eq!(elf_machine(arch_name), Some(machine), "{arch_name}");
+
+            // The arch check names the machine of the ELF file
+            let mut bytes = crate::elf::tests::elf64();
+            bytes[0x12..0x14].copy_from_slice(&machine.to_le_bytes());
+            let header = crate::elf::Elf::parse(&bytes).unwrap().header;
+            assert!(header.machine_name().is_some(), "{arch_name}");
+        }
+        assert_eq!(elf_machine("wasm32"), None);
+    }
+
+    #[test]
+    fn elf_output_targets() {
+        let target = |arch_name, is64, big_endian| elf_output_target(arch_name, is64, big_endian);
+
+        assert_eq!(
+            target("thumb", false, false).as_deref(),
+            Some("elf32-littlearm")
+        );
+        assert_eq!(
+            target("armeb", false, true).as_deref(),
+            Some("elf32-bigarm")
+        );
+        assert_eq!(
+            target("x86-64", true, false).as_deref(),
+            Some("elf64-x86-64")
+        );
+        assert_eq!(
+            target("mips", false, true).as_deref(),
+            Some("elf32-tradbigmips")
+        );
+        assert_eq!(
+            target("ppc64le", true, false).as_deref(),
+            Some("elf64-powerpcle")
+        );
+        assert_eq!(
+            target("riscv32", false, false).as_deref(),
+            Some("elf32-littleriscv")
+        );
+        assert_eq!(target("wasm32", false, false), None);
+    }
+
+    #[test]
+    fn cpu_flags_of_rustflags() {
+        assert_eq!(cpu_flags::<&str>(&[]), (None, None));
+        assert_eq!(
+            cpu_flags(&["-C", "target-cpu=cortex-m4", "-Ctarget-cpu=cortex-m7"]),
+            (Some("cortex-m7".to_owned()), None)
+        );
+        assert_eq!(cpu_flags(&["-Ctarget-cpu=native"]), (None, None));
+        assert_eq!(
+            cpu_flags(&[
+                "--codegen=target-feature=+neon,+crt-static",
+                "--codegen",
+                "target-feature=-fp16",
+                "-Copt-level=3",
+            ]),
+            (None, Some("+neon,-fp16".to_owned()))
+        );
+        // A dangling `-C`
+        assert_eq!(cpu_flags(&["-C"]), (None, None));
+    }
+
+    #[test]
+    fn loongarch_arch_names() {
+        assert_eq!(
+            arch_name("loongarch64", "little", "loongarch64-unknown-linux-gnu"),
+            "loongarch64"
+        );
+        assert_eq!(
+            arch_name("loongarch32", "little", "loongarch32-unknown-none"),
+            "loongarch32"
+        );
+    }
+
+    #[test]
+    fn bpf_arch_names() {
+        assert_eq!(arch_name("bpf", "little", "bpfel-unknown-none"), "bpfel");
+        assert_eq!(arch_name("bpf", "big", "bpfeb-unknown-none"), "bpfeb");
+    }
+
+    #[test]
+    fn arch_names() {
+        assert_eq!(arch_name("arm", "little", "thumbv7em-none-eabihf"), "thumb");
+        assert_eq!(
+            arch_name("arm", "big", "thumbv7neon-unknown-linux-gnueabihf"),
+            "thumbeb"
+        );
+        assert_eq!(
+            arch_name("arm", "little", "armv7-unknown-linux-gnueabihf"),
+            "arm"
+        );
+        assert_eq!(arch_name("arm", "big", "armebv7r-none-eabi"), "armeb");
+        assert_eq!(
+            arch_name("aarch64", "big", "aarch64_be-unknown-linux-gnu"),
+            "aarch64_be"
+        );
+        assert_eq!(
+            arch_name("mips", "little", "mipsel-unknown-linux-gnu"),
+            "mipsel"
+        );
+        assert_eq!(
+            arch_name("mips64", "little", "mips64el-unknown-linux-gnuabi64"),
+            "mips64el"
+        );
+        assert_eq!(
+            arch_name("powerpc", "big", "powerpc-unknown-linux-gnu"),
+            "ppc32"
+        );
+        assert_eq!(
+            arch_name("powerpc64", "big", "powerpc64-unknown-linux-gnu"),
+            "ppc64"
+        );
+        assert_eq!(
+            arch_name("powerpc64", "little", "powerpc64le-unknown-linux-gnu"),
+            "ppc64le"
+        );
+        assert_eq!(
+            arch_name("sparc64", "big", "sparc64-unknown-linux-gnu"),
+            "sparcv9"
+        );
+        assert_eq!(
+            arch_name("s390x", "big", "s390x-unknown-linux-gnu"),
+            "systemz"
+        );
+        assert_eq!(
+            arch_name("x86_64", "little", "x86_64-unknown-linux-gnu"),
+            "x86-64"
+        );
+        assert_eq!(
+            arch_name("riscv32", "little", "riscv32imac-unknown-none-elf"),
+            "riscv32"
+        );
+        assert_eq!(arch_name("x86", "little", "i686-unknown-linux-gnu"), "x86");
+    }
+}
@@ -0,0 +1,329 @@
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::CharIndices;
+use std::{fmt, fs};
+
+use anyhow::{bail, Context, Result};
+
+// Just enough of a parser for the `MEMORY` command of the linker scripts, the `memory.x` of the
+// embedded crates, to tell how full their regions are.
+
+/// A region of the `MEMORY` command, e.g. `FLASH : ORIGIN = 0x08000000, LENGTH = 256K`
+pub struct Region {
+    pub name: String,
+    pub origin: u64,
+    pub length: u64,
+}
+
+impl Region {
+    pub fn contains(&self, addr: u64) -> bool {
+        addr >= self.origin && addr - self.origin < self.length
+    }
+}
+
+/// Reads the regions of the `MEMORY` command of the linker script at `path`
+pub fn read(path: &Path) -> Result<Vec<Region>> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+    parse(&text).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+fn parse(text: &str) -> Result<Vec<Region>> {
+    let mut tokens = Tokens::new(text).peekable();
+
+    // Skip everything up to `MEMORY {`
+    loop {
+        match tokens.next() {
+            Some(Token::Ident("MEMORY")) => break,
+            Some(_) => {}
+            None => bail!("no `MEMORY` command"),
+        }
+    }
+    expect(&mut tokens, Token::Punct('{'))?;
+
+    let mut regions = vec![];
+    loop {
+        let name = match tokens.next() {
+            Some(Token::Punct('}')) => break,
+            Some(Token::Ident(name)) => name,
+            Some(token) => bail!("expected a region name, found {token}"),
+            None => bail!("unterminated `MEMORY` command"),
+        };
+
+        // The attributes, e.g. `(rx)`
+        if tokens.peek() == Some(&Token::Punct('(')) {
+            while tokens
+                .next()
+                .is_some_and(|token| token != Token::Punct(')'))
+            {}
+        }
+        expect(&mut tokens, Token::Punct(':'))?;
+
+        let origin = assignment(&mut tokens, &["ORIGIN", "org", "o"], &regions)?;
+        if tokens.peek() == Some(&Token::Punct(',')) {
+            tokens.next();
+        }
+        let length = assignment(&mut tokens, &["LENGTH", "len", "l"], &regions)?;
+
+        regions.push(Region {
+            name: name.to_owned(),
+            origin,
+            length,
+        });
+    }
+
+    Ok(regions)
+}
+
+fn expect<'a>(tokens: &mut impl Iterator<Item = Token<'a>>, expected: Token<'_>) -> Result<()> {
+    match tokens.next() {
+        Some(token) if token == expected => Ok(()),
+        Some(token) => bail!("expected {expected}, found {token}"),
+        None => bail!("expected {expected}, found the end of the file"),
+    }
+}
+
+// `$key = $expression`, where `key` is one of `keys`
+fn assignment<'a>(
+    tokens: &mut Peekable<impl Iterator<Item = Token<'a>>>,
+    keys: &[&str],
+    regions: &[Region],
+) -> Result<u64> {
+    match tokens.next() {
+        Some(Token::Ident(key)) if keys.contains(&key) => {}
+        Some(token) => bail!("expected `{}`, found {token}", keys[0]),
+        None => bail!("expected `{}`, found the end of the file", keys[0]),
+    }
+    expect(tokens, Token::Punct('='))?;
+
+    // Only sums and differences, e.g. `256K - 4K` or `ORIGIN(RAM) + LENGTH(RAM)`
+    let mut value = term(tokens, regions)?;
+    loop {
+        match tokens.peek() {
+            Some(Token::Punct('+')) => {
+                tokens.next();
+                value = value.wrapping_add(term(tokens, regions)?);
+            }
+            Some(Token::Punct('-')) => {
+                tokens.next();
+                value = value.wrapping_sub(term(tokens, regions)?);
+            }
+            _ => return Ok(value),
+        }
+    }
+}
+
+fn term<'a>(tokens: &mut impl Iterator<Item = Token<'a>>, regions: &[Region]) -> Result<u64> {
+    match tokens.next() {
+        Some(Token::Number(n)) => number(n),
+        Some(Token::Ident(function @ ("ORIGIN" | "LENGTH"))) => {
+            expect(tokens, Token::Punct('('))?;
+            let name = match tokens.next() {
+                Some(Token::Ident(name)) => name,
+                _ => bail!("expected a region name in `{function}(..)`"),
+            };
+            expect(tokens, Token::Punct(')'))?;
+
+            let region = regions
+                .iter()
+                .find(|region| region.name == name)
+                .with_context(|| format!("unknown region `{name}`"))?;
+            Ok(if function == "ORIGIN" {
+                region.origin
+            } else {
+                region.length
+            })
+        }
+        Some(token) => bail!("expected a number, found {token}"),
+        None => bail!("expected a number, found the end of the file"),
+    }
+}
+
+// Decimal or hexadecimal (`0x`) numbers with an optional `K` or `M` multiplier
+fn number(s: &str) -> Result<u64> {
+    let (digits, multiplier) = match s.as_bytes().last() {
+        Some(b'K' | b'k') => (&s[..s.len() - 1], 1024),
+        Some(b'M' | b'm') => (&s[..s.len() - 1], 1024 * 1024),
+        _ => (s, 1),
+    };
+
+    let n = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => digits.parse(),
+    };
+
+    n.ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .with_context(|| format!("invalid number `{s}`"))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Ident(&'a str),
+    Number(&'a str),
+    Punct(char),
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(s) | Token::Number(s) => write!(f, "`{s}`"),
+            Token::Punct(c) => write!(f, "`{c}`"),
+        }
+    }
+}
+
+struct Tokens<'a> {
+    text: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(text: &'a str) -> Self {
+        Tokens {
+            text,
+            chars: text.char_indices().peekable(),
+        }
+    }
+
+    // The end of the word that starts at `start`
+    fn word_end(&mut self, start: usize) -> usize {
+        let mut end = start;
+        while let Some(&(i, c)) = self.chars.peek() {
+            if !(c.is_alphanumeric() || matches!(c, '_' | '.' | '$')) {
+                break;
+            }
+            end = i + c.len_utf8();
+            self.chars.next();
+        }
+        end
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        loop {
+            let (start, c) = self.chars.next()?;
+            match c {
+                c if c.is_whitespace() => {}
+                // `/* .. */` comments
+                '/' if self.chars.peek().is_some_and(|&(_, c)| c == '*') => {
+                    self.chars.next();
+                    let mut star = false;
+                    for (_, c) in self.chars.by_ref() {
+                        if star && c == '/' {
+                            break;
+                        }
+                        star = c == '*';
+                    }
+                }
+                c if c.is_ascii_digit() => {
+                    let end = self.word_end(start + 1);
+                    return Some(Token::Number(&self.text[start..end]));
+                }
+                c if c.is_alphabetic() || matches!(c, '_' | '.' | '$') => {
+                    let end = self.word_end(start + c.len_utf8());
+                    return Some(Token::Ident(&self.text[start..end]));
+                }
+                c => return Some(Token::Punct(c)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regions(text: &str) -> Vec<(String, u64, u64)> {
+        parse(text)
+            .unwrap()
+            .into_iter()
+            .map(|region| (region.name, region.origin, region.length))
+            .collect()
+    }
+
+    #[test]
+    fn parses_regions() {
+        let text = "
+/* Linker script for the STM32F303VCT6 */
+MEMORY
+{
+  /* NOTE 1 K = 1 KiBi = 1024 bytes */
+  FLASH (rx) : ORIGIN = 0x08000000, LENGTH = 256K
+  RAM : org = 0x20000000, len = 40K
+  CCMRAM : o = 0x10000000, l = 8k
+}
+
+/* The stack starts at the end of RAM */
+_stack_start = ORIGIN(RAM) + LENGTH(RAM);
+";
+        assert_eq!(
+            regions(text),
+            [
+                ("FLASH".to_owned(), 0x0800_0000, 256 * 1024),
+                ("RAM".to_owned(), 0x2000_0000, 40 * 1024),
+                ("CCMRAM".to_owned(), 0x1000_0000, 8 * 1024),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_expressions() {
+        let text = "MEMORY {
+  FLASH : ORIGIN = 0x00000000, LENGTH = 1M - 4K
+  CONFIG : ORIGIN = ORIGIN(FLASH) + LENGTH(FLASH), LENGTH = 4K
+}";
+        assert_eq!(
+            regions(text),
+            [
+                ("FLASH".to_owned(), 0, 1024 * 1024 - 4 * 1024),
+                ("CONFIG".to_owned(), 1024 * 1024 - 4 * 1024, 4 * 1024),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for (text, error) in [
+            ("SECTIONS {}", "no `MEMORY` command"),
+            (
+                "MEMORY { FLASH : ORIGIN = 0",
+                "expected `LENGTH`, found the end of the file",
+            ),
+            (
+                "MEMORY { FLASH : ORIGIN = 0, LENGTH = 4G }",
+                "invalid number `4G`",
+            ),
+            (
+                "MEMORY { FLASH : ORIGIN = ORIGIN(ROM), LENGTH = 0 }",
+                "unknown region `ROM`",
+            ),
+            (
+                "MEMORY { 0 : ORIGIN = 0, LENGTH = 0 }",
+                "expected a region name, found `0`",
+            ),
+        ] {
+            assert_eq!(parse(text).err().unwrap().to_string(), error, "{text}");
+        }
+    }
+
+    #[test]
+    fn contains() {
+        let region = Region {
+            name: "RAM".to_owned(),
+            origin: 0x2000_0000,
+            length: 0x100,
+        };
+        assert!(region.contains(0x2000_0000));
+        assert!(region.contains(0x2000_00ff));
+        assert!(!region.contains(0x2000_0100));
+        assert!(!region.contains(0x1fff_ffff));
+    }
+}
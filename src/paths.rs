@@ -0,0 +1,3 @@
+//! The directories of the toolchain the tools are looked up in
+
+pub use crate::rustc::{rustlib, sysroot};
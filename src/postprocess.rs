@@ -1,58 +1,1461 @@
 use std::borrow::Cow;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{HashMap, HashSet};
 use std::str;
+use std::sync::OnceLock;
 
+use anyhow::{bail, Result};
 use regex::{Captures, Regex};
 
+use crate::elf::{self, Segment};
+use crate::memory;
+
 // Here we post process the output of some tools to improve. If the output of the tool is not valid
 // UTF-8 then we don't touch it.
 
 // This pass demangles *all* the Rust symbols in the input
 pub fn demangle(bytes: &[u8]) -> Cow<'_, [u8]> {
-    let re = Regex::new(r"_Z.+?E\b").expect("BUG: Malformed Regex");
+    Demangler::default().demangle(bytes)
+}
+
+fn demangle_str(text: &str) -> Cow<'_, str> {
+    Demangler::default().demangle_str(text)
+}
+
+/// Demangles the Rust symbols of a stream of lines. The same symbols show up over and over in
+/// disassembly so each of them is demangled once, for all the lines.
+#[derive(Default)]
+pub struct Demangler {
+    cache: HashMap<String, String>,
+}
+
+impl Demangler {
+    pub fn demangle<'a>(&mut self, bytes: &'a [u8]) -> Cow<'a, [u8]> {
+        if let Ok(text) = str::from_utf8(bytes) {
+            match self.demangle_str(text) {
+                Cow::Borrowed(s) => s.as_bytes().into(),
+                Cow::Owned(s) => s.into_bytes().into(),
+            }
+        } else {
+            bytes.into()
+        }
+    }
+
+    fn demangle_str<'a>(&mut self, text: &'a str) -> Cow<'a, str> {
+        static RE: OnceLock<Regex> = OnceLock::new();
+        // Legacy (`_ZN..E`) and v0 (`_R..`) mangled symbols. Both stop before the `+0x8` of the
+        // `<symbol+0x8>` operands of objdump so the offset is kept as is.
+        let re = RE.get_or_init(|| {
+            Regex::new(r"_Z.+?E\b|_R[0-9A-Za-z_$.]+").expect("BUG: Malformed Regex")
+        });
 
+        re.replace_all(text, |cs: &Captures<'_>| {
+            let mangled = cs.get(0).unwrap().as_str();
+            self.cache
+                .entry(mangled.to_owned())
+                .or_insert_with(|| format!("{}", rustc_demangle::demangle(mangled)))
+                .clone()
+        })
+    }
+}
+
+// Like `demangle` but in the rows of the GNU style symbol tables of `readobj` only the `Name`
+// column is demangled, leaving the `Value`, `Type`, `Bind`, etc. columns untouched
+pub fn readobj(bytes: &[u8]) -> Cow<'_, [u8]> {
     if let Ok(text) = str::from_utf8(bytes) {
-        match re.replace_all(text, |cs: &Captures<'_>| {
-            format!("{}", rustc_demangle::demangle(cs.get(0).unwrap().as_str()))
-        }) {
-            Cow::Borrowed(s) => s.as_bytes().into(),
-            Cow::Owned(s) => s.into_bytes().into(),
+        let mut s = String::with_capacity(text.len());
+        let mut in_symbol_table = false;
+
+        for line in text.lines() {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields.first() == Some(&"Num:") && fields.last() == Some(&"Name") {
+                in_symbol_table = true;
+            } else if in_symbol_table {
+                match readobj_symbol_name(line) {
+                    Some(start) => {
+                        s.push_str(&line[..start]);
+                        s.push_str(&demangle_str(&line[start..]));
+                        s.push('\n');
+                        continue;
+                    }
+                    None => in_symbol_table = false,
+                }
+            }
+
+            s.push_str(&demangle_str(line));
+            s.push('\n');
         }
+
+        s.into_bytes().into()
     } else {
         bytes.into()
     }
 }
 
-// This pass turns the addresses in the output of `size -A` into hexadecimal format
-pub fn size(bytes: &[u8]) -> Cow<[u8]> {
+// Returns the offset of the `Name` column in a symbol table row like
+// `    2: 08000400     8 FUNC    GLOBAL DEFAULT     2 _ZN3app4main17h0123456789abcdefE`
+fn readobj_symbol_name(line: &str) -> Option<usize> {
+    let mut fields = line
+        .split_whitespace()
+        .map(|field| (field.as_ptr() as usize - line.as_ptr() as usize, field));
+
+    let (_, num) = fields.next()?;
+    if !num.strip_suffix(':')?.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    // Value, Size, Type, Bind and Vis
+    for _ in 0..5 {
+        fields.next()?;
+    }
+
+    // Ndx, which is preceded by e.g. `[<other>: 0x88]` if `st_other` has unknown bits set
+    let (_, mut field) = fields.next()?;
+    if field.starts_with("[<other>") {
+        while !field.ends_with(']') {
+            (_, field) = fields.next()?;
+        }
+        fields.next()?;
+    }
+
+    // Unnamed symbols have no `Name` column at all
+    Some(fields.next().map_or(line.len(), |(start, _)| start))
+}
+
+// This pass drops the source lines of `objdump --source` that repeat the previous source line of
+// the same function, i.e. that only have instructions between them
+pub fn dedup_source(bytes: &[u8]) -> Cow<'_, [u8]> {
     if let Ok(text) = str::from_utf8(bytes) {
-        let mut s = text
-            .lines()
-            .map(|line| -> Cow<'_, str> {
-                match line
+        let mut s = String::with_capacity(text.len());
+        let mut last_source = None;
+
+        for line in text.lines() {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with(';') {
+                // Source lines are prefixed with `; `
+                if last_source == Some(trimmed) {
+                    continue;
+                }
+                last_source = Some(trimmed);
+            } else if line.is_empty() || line.ends_with(">:") {
+                // A new function (`08000400 <main>:`) starts after a blank line
+                last_source = None;
+            }
+
+            s.push_str(line);
+            s.push('\n');
+        }
+
+        s.into_bytes().into()
+    } else {
+        bytes.into()
+    }
+}
+
+/// Output format of `nm`, see its `--format` flag
+#[derive(Clone, Copy)]
+pub enum NmFormat {
+    Bsd,
+    Darwin,
+    JustSymbols,
+    Posix,
+    Sysv,
+}
+
+impl NmFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bsd" => Some(NmFormat::Bsd),
+            "darwin" => Some(NmFormat::Darwin),
+            "just-symbols" => Some(NmFormat::JustSymbols),
+            "posix" => Some(NmFormat::Posix),
+            "sysv" => Some(NmFormat::Sysv),
+            _ => None,
+        }
+    }
+
+    /// Detects the format selected by the flags passed to `nm`, `format` if they select none
+    pub fn from_args(mut format: NmFormat, args: &[&str]) -> Self {
+        let mut args = args.iter();
+        while let Some(&arg) = args.next() {
+            let value = match arg {
+                "-P" | "--portability" => Some("posix"),
+                "-j" => Some("just-symbols"),
+                "-f" | "--format" => args.next().copied(),
+                _ => arg
+                    .strip_prefix("--format=")
+                    .or_else(|| arg.strip_prefix("-f")),
+            };
+
+            if let Some(value) = value.and_then(NmFormat::from_name) {
+                format = value;
+            }
+        }
+        format
+    }
+
+    /// Extracts the symbol name from a line of `nm` output in this format
+    fn symbol_name(self, line: &str) -> Option<&str> {
+        self.row(line).map(|row| row.name)
+    }
+
+    /// Parses a line of `nm` output in this format
+    fn row(self, line: &str) -> Option<NmRow<'_>> {
+        // With multiple input files (e.g. the objects of an rlib) each file gets a `$file:` header
+        if line.ends_with(':') && !line.contains(char::is_whitespace) {
+            return None;
+        }
+
+        let hex = |s: &str| u64::from_str_radix(s.trim(), 16).ok();
+        match self {
+            // `$address $type $name`, or `$address $size $type $name` with `--print-size`, followed
+            // by `\t$file:$line` with `--line-numbers`. Undefined symbols have no address.
+            NmFormat::Bsd | NmFormat::Darwin => {
+                let fields = line
+                    .split('\t')
+                    .next()?
                     .split_whitespace()
-                    .nth(2)
-                    .and_then(|part| part.parse::<u64>().ok().map(|addr| (part, addr)))
+                    .collect::<Vec<_>>();
+                let (numbers, [_type, name]) = fields.split_at(fields.len().checked_sub(2)?) else {
+                    return None;
+                };
+                Some(NmRow {
+                    name,
+                    address: numbers.first().and_then(|n| hex(n)),
+                    size: numbers.get(1).and_then(|n| hex(n)),
+                })
+            }
+            NmFormat::JustSymbols => {
+                Some(line.trim())
+                    .filter(|name| !name.is_empty())
+                    .map(|name| NmRow {
+                        name,
+                        address: None,
+                        size: None,
+                    })
+            }
+            // `$name $type $address $size`
+            NmFormat::Posix => {
+                let mut fields = line.split_whitespace();
+                Some(NmRow {
+                    name: fields.next()?,
+                    address: fields.nth(1).and_then(hex),
+                    size: fields.next().and_then(hex),
+                })
+            }
+            // `$name |$address|$class|$type|$size|$line|$section`, the header has no `|`
+            NmFormat::Sysv => {
+                let mut fields = line.split('|');
+                let name = fields.next()?.trim();
+                let address = fields.next()?;
+                Some(NmRow {
+                    name,
+                    address: hex(address),
+                    size: fields.nth(2).and_then(hex),
+                })
+            }
+        }
+    }
+}
+
+/// A symbol row of the `nm` output
+struct NmRow<'a> {
+    name: &'a str,
+    address: Option<u64>,
+    size: Option<u64>,
+}
+
+/// Sort key of the symbol rows of the `nm` output
+#[derive(Clone, Copy, PartialEq)]
+pub enum SymbolSort {
+    Address,
+    Name,
+    Size,
+}
+
+impl SymbolSort {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "address" => Some(SymbolSort::Address),
+            "name" => Some(SymbolSort::Name),
+            "size" => Some(SymbolSort::Size),
+            _ => None,
+        }
+    }
+}
+
+// Sorts the symbol rows of the `nm` output by the `keys`, the first one in reverse order if
+// `reverse` is set. Ties are broken by name and then by address so the order doesn't depend on
+// the one of `nm`. The rows of each file of a multi-file output are sorted separately.
+fn sort_symbols(text: &str, options: &NmOptions) -> String {
+    // `(line, row, name)`, where `name` is the demangled name if the output is demangled
+    type Row<'a> = (&'a str, NmRow<'a>, String);
+
+    fn flush(rows: &mut Vec<Row<'_>>, options: &NmOptions, s: &mut String) {
+        let keys = options
+            .sort
+            .iter()
+            .copied()
+            .chain([SymbolSort::Name, SymbolSort::Address])
+            .collect::<Vec<_>>();
+        rows.sort_by(|(_, a, a_name), (_, b, b_name)| {
+            for (i, key) in keys.iter().enumerate() {
+                let ordering = match key {
+                    SymbolSort::Address => a.address.cmp(&b.address),
+                    SymbolSort::Name => a_name.cmp(b_name),
+                    SymbolSort::Size => a.size.cmp(&b.size),
+                };
+                let ordering = if i == 0 && options.reverse {
+                    ordering.reverse()
+                } else {
+                    ordering
+                };
+                if ordering.is_ne() {
+                    return ordering;
+                }
+            }
+            Ordering::Equal
+        });
+
+        for (line, _, _) in rows.drain(..) {
+            s.push_str(line);
+            s.push('\n');
+        }
+    }
+
+    let mut s = String::new();
+    let mut rows = vec![];
+    for line in text.lines() {
+        match options.format.row(line) {
+            Some(row) => {
+                let name = if options.demangle {
+                    format!("{:#}", rustc_demangle::demangle(row.name))
+                } else {
+                    row.name.to_owned()
+                };
+                rows.push((line, row, name));
+            }
+            // Headers and blank lines
+            None => {
+                flush(&mut rows, options, &mut s);
+                s.push_str(line);
+                s.push('\n');
+            }
+        }
+    }
+    flush(&mut rows, options, &mut s);
+    s
+}
+
+/// Options of the `nm` pass
+pub struct NmOptions {
+    pub demangle: bool,
+    /// Only print the symbol names, one per line
+    pub names_only: bool,
+    /// Only keep the rows of these (mangled) symbols
+    pub symbols: Option<HashSet<String>>,
+    pub format: NmFormat,
+    /// Sort the symbol rows by these keys, none keeps the order of `nm`
+    pub sort: Vec<SymbolSort>,
+    /// Reverse the order of the first sort key
+    pub reverse: bool,
+}
+
+// This pass applies the `options` to the output of `nm`
+pub fn nm<'a>(bytes: &'a [u8], options: &NmOptions) -> Cow<'a, [u8]> {
+    let text = match str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(_) => return bytes.into(),
+    };
+
+    let text: Cow<'_, str> = if options.sort.is_empty() {
+        text.into()
+    } else {
+        sort_symbols(text, options).into()
+    };
+
+    let text = if options.names_only || options.symbols.is_some() {
+        let mut s = String::new();
+        for line in text.lines() {
+            let line = match options.format.symbol_name(line) {
+                Some(name)
+                    if options
+                        .symbols
+                        .as_ref()
+                        .is_some_and(|symbols| !symbols.contains(name)) =>
                 {
-                    // the lines to postprocess have the form ".section_name 100 1024" where
-                    // the second number is the address
-                    Some((needle, addr)) if line.starts_with('.') => {
-                        let pos = line.rfind(needle).unwrap();
-                        let hex_addr = format!("{addr:#x}");
-                        let start = pos + needle.as_bytes().len() - hex_addr.as_bytes().len();
-
-                        format!("{}{}", &line[..start], hex_addr).into()
-                    }
-                    _ => line.into(),
+                    continue
                 }
+                Some(name) if options.names_only => name,
+                // Headers
+                _ if options.names_only => continue,
+                _ => line,
+            };
+            s.push_str(line);
+            s.push('\n');
+        }
+        s.into()
+    } else {
+        text
+    };
+
+    let text = match text {
+        Cow::Borrowed(text) if options.demangle => demangle_str(text),
+        Cow::Owned(text) if options.demangle => demangle_str(&text).into_owned().into(),
+        text => text,
+    };
+
+    match text {
+        Cow::Borrowed(s) => s.as_bytes().into(),
+        Cow::Owned(s) => s.into_bytes().into(),
+    }
+}
+
+/// Order of the section rows in the System V (`size -A`) output
+#[derive(Clone, Copy)]
+pub enum SectionSort {
+    /// Largest section first
+    Size,
+    Name,
+    /// Lowest address first
+    Vma,
+}
+
+/// Options of the `size` pass
+#[derive(Default)]
+pub struct SizeOptions {
+    pub sort: Option<SectionSort>,
+    /// Combine the sections that share a prefix into a single row
+    pub group_by_prefix: bool,
+    /// Program headers of the artifact, used to add a load address column to the System V output
+    pub segments: Option<Vec<Segment>>,
+    /// Add the sizes in KiB, MiB, etc. next to the byte counts
+    pub human: bool,
+    /// Only print the total size
+    pub totals_only: bool,
+}
+
+/// Formats `n` bytes in the largest binary unit that keeps the value under 1024, with one decimal,
+/// e.g. `1.5 KiB`. A value that would round up to 1024 is shown in the next unit instead.
+fn human_size(n: u64) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+    if n < 1024 {
+        return format!("{n} B");
+    }
+
+    let mut value = n as f64 / 1024.0;
+    let mut units = UNITS.iter();
+    let mut unit = units.next().unwrap();
+    while (value * 10.0).round() >= 10240.0 {
+        match units.next() {
+            Some(next) => {
+                value /= 1024.0;
+                unit = next;
+            }
+            None => break,
+        }
+    }
+    format!("{value:.1} {unit}")
+}
+
+/// The cell of a size, with its human readable form if requested and the size is at least 1 KiB
+fn size_cell(radix: Radix, n: u64, human: bool) -> String {
+    if human && n >= 1024 {
+        format!("{} ({})", radix.format(n), human_size(n))
+    } else {
+        radix.format(n)
+    }
+}
+
+/// The radix `size` prints numbers in, see its `--radix` flag
+#[derive(Clone, Copy, PartialEq)]
+enum Radix {
+    Octal,
+    Decimal,
+    Hexadecimal,
+}
+
+impl Radix {
+    // `size` formats numbers with `printf("%#o")` / `printf("%#x")`
+    fn detect<'a>(numbers: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut radix = Radix::Decimal;
+        for n in numbers {
+            if n.starts_with("0x") {
+                return Radix::Hexadecimal;
+            } else if n.len() > 1 && n.starts_with('0') {
+                radix = Radix::Octal;
+            }
+        }
+        radix
+    }
+
+    fn parse(self, s: &str) -> Option<u64> {
+        match self {
+            Radix::Octal => u64::from_str_radix(s, 8).ok(),
+            Radix::Decimal => s.parse().ok(),
+            Radix::Hexadecimal => u64::from_str_radix(s.strip_prefix("0x").unwrap_or(s), 16).ok(),
+        }
+    }
+
+    fn format(self, n: u64) -> String {
+        match self {
+            Radix::Decimal => n.to_string(),
+            _ if n == 0 => "0".to_owned(),
+            Radix::Octal => format!("{n:#o}").replacen("0o", "0", 1),
+            Radix::Hexadecimal => format!("{n:#x}"),
+        }
+    }
+}
+
+/// A row of a System V table
+struct Section<'a> {
+    name: &'a str,
+    size: u64,
+    addr: u64,
+    /// Load address, only filled in if requested
+    lma: Option<u64>,
+}
+
+impl<'a> Section<'a> {
+    /// The name up to the second `.`, e.g. `.text` for `.text.main`
+    fn prefix(&self) -> &'a str {
+        let name = self.name;
+        match name.get(1..).and_then(|rest| rest.find('.')) {
+            Some(i) => &name[..i + 1],
+            None => name,
+        }
+    }
+}
+
+/// A System V (`size -A`) table, i.e. the lines from the `section size addr` header to the
+/// `Total` row
+struct SysvTable<'a> {
+    sections: Vec<Section<'a>>,
+    total: u64,
+    radix: Radix,
+    human: bool,
+}
+
+impl<'a> SysvTable<'a> {
+    /// Parses the table whose header is `lines[0]`, returning it and the number of lines it spans
+    fn parse(lines: &[&'a str]) -> Option<(Self, usize)> {
+        let mut lines = lines
+            .iter()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>());
+
+        if lines.next()? != ["section", "size", "addr"] {
+            return None;
+        }
+
+        let mut rows = vec![];
+        let total = loop {
+            match *lines.next()? {
+                ["Total", total] => break total,
+                [name, size, addr] => rows.push((name, size, addr)),
+                _ => return None,
+            }
+        };
+
+        let radix = Radix::detect(rows.iter().map(|&(_, size, _)| size).chain([total]));
+        let sections = rows
+            .iter()
+            .map(|&(name, size, addr)| {
+                Some(Section {
+                    name,
+                    size: radix.parse(size)?,
+                    addr: radix.parse(addr)?,
+                    lma: None,
+                })
             })
-            .collect::<Vec<_>>()
-            .join("\n");
+            .collect::<Option<Vec<_>>>()?;
+
+        let table = SysvTable {
+            sections,
+            total: radix.parse(total)?,
+            radix,
+            human: false,
+        };
+        let len = table.sections.len() + 2;
+        Some((table, len))
+    }
+
+    // Renders the table the way `size` does, except that addresses are always in hexadecimal
+    fn render(&self, s: &mut String) {
+        let with_lma = self.sections.iter().all(|section| section.lma.is_some());
+
+        let mut header = vec!["section".to_owned(), "size".to_owned(), "addr".to_owned()];
+        if with_lma {
+            header.push("lma".to_owned());
+        }
+
+        let mut rows = vec![header];
+        for section in &self.sections {
+            let mut row = vec![
+                section.name.to_owned(),
+                size_cell(self.radix, section.size, self.human),
+                Radix::Hexadecimal.format(section.addr),
+            ];
+            row.extend(
+                section
+                    .lma
+                    .filter(|_| with_lma)
+                    .map(|lma| Radix::Hexadecimal.format(lma)),
+            );
+            rows.push(row);
+        }
+        rows.push(vec![
+            "Total".to_owned(),
+            size_cell(self.radix, self.total, self.human),
+        ]);
+
+        let mut widths = vec![0; rows[0].len()];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len() + 2);
+            }
+        }
+
+        for row in &rows {
+            let mut cells = row.iter().zip(&widths);
+            if let Some((name, width)) = cells.next() {
+                s.push_str(&format!("{name:<width$}"));
+            }
+            for (cell, width) in cells {
+                s.push_str(&format!(" {cell:>width$}"));
+            }
+            s.push('\n');
+        }
+    }
+}
+
+/// A row of a Berkeley (`size -B`) table
+struct BerkeleyRow<'a> {
+    text: u64,
+    data: u64,
+    bss: u64,
+    /// The radix of `text`, `data` and `bss`, `dec` is always decimal
+    radix: Radix,
+    dec: u64,
+    hex: &'a str,
+    filename: &'a str,
+}
+
+impl<'a> BerkeleyRow<'a> {
+    /// Parses the rows of the Berkeley table whose header is `lines[0]`
+    fn parse_table(lines: &[&'a str]) -> Option<Vec<Self>> {
+        // `$text\t$data\t$bss\t$dec\t$hex\t$filename`
+        fn split(line: &str) -> Vec<&str> {
+            line.splitn(6, '\t').map(str::trim).collect()
+        }
+
+        if split(lines.first()?) != ["text", "data", "bss", "dec", "hex", "filename"] {
+            return None;
+        }
+
+        let mut rows = vec![];
+        for line in &lines[1..] {
+            let fields = split(line);
+            let [text, data, bss, dec, hex, filename] = fields[..] else {
+                break;
+            };
 
-        // `text.lines()` loses the trailing newline so we restore it here
+            let radix = Radix::detect([text, data, bss]);
+            rows.push(BerkeleyRow {
+                text: radix.parse(text)?,
+                data: radix.parse(data)?,
+                bss: radix.parse(bss)?,
+                radix,
+                dec: dec.parse().ok()?,
+                hex,
+                filename,
+            });
+        }
+        Some(rows)
+    }
+}
+
+// Renders a Berkeley table with the human readable form of the sizes
+fn render_berkeley_human(rows: &[BerkeleyRow<'_>], s: &mut String) {
+    let mut cells = vec![["text", "data", "bss", "dec", "hex", "filename"].map(str::to_owned)];
+    for row in rows {
+        cells.push([
+            size_cell(row.radix, row.text, true),
+            size_cell(row.radix, row.data, true),
+            size_cell(row.radix, row.bss, true),
+            size_cell(Radix::Decimal, row.dec, true),
+            row.hex.to_owned(),
+            row.filename.to_owned(),
+        ]);
+    }
+
+    let mut widths = [7; 5];
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    for row in &cells {
+        for (cell, width) in row.iter().zip(&widths) {
+            s.push_str(&format!("{cell:>width$}\t"));
+        }
+        s.push_str(&row[5]);
         s.push('\n');
+    }
+}
+
+// The total size reported by the System V and Berkeley tables of the `size` output, summed if
+// there are several of them, e.g. one per object file of a library, and the radix of the tables
+fn total(lines: &[&str]) -> Option<(u64, Radix)> {
+    let mut total = None::<(u64, Radix)>;
+    for i in 0..lines.len() {
+        let (n, radix) = if let Some((table, _)) = SysvTable::parse(&lines[i..]) {
+            (table.total, table.radix)
+        } else if let Some(rows) = BerkeleyRow::parse_table(&lines[i..]) {
+            let dec = rows.iter().fold(0u64, |sum, row| sum.wrapping_add(row.dec));
+            (dec, Radix::Decimal)
+        } else {
+            continue;
+        };
+
+        total = Some(match total {
+            Some((sum, _)) => (sum.wrapping_add(n), radix),
+            None => (n, radix),
+        });
+    }
+    total
+}
+
+// This pass turns the addresses in the output of `size -A` into hexadecimal format and applies
+// the requested `options`. The Berkeley format is only changed to add human readable sizes.
+pub fn size<'a>(bytes: &'a [u8], options: &SizeOptions) -> Cow<'a, [u8]> {
+    if let Ok(text) = str::from_utf8(bytes) {
+        let lines = text.lines().collect::<Vec<_>>();
+
+        if options.totals_only {
+            return match total(&lines) {
+                Some((total, radix)) => format!("{}\n", size_cell(radix, total, options.human))
+                    .into_bytes()
+                    .into(),
+                // Not a format we know, e.g. `--format=darwin`
+                None => bytes.into(),
+            };
+        }
+
+        let mut s = String::new();
+        let mut i = 0;
+        while i < lines.len() {
+            if let Some((mut table, len)) = SysvTable::parse(&lines[i..]) {
+                // A mismatch means we misparsed the output, e.g. of a newer `size`
+                let sum = table
+                    .sections
+                    .iter()
+                    .fold(0u64, |sum, section| sum.wrapping_add(section.size));
+                if sum != table.total {
+                    eprintln!(
+                        "warning: the sections add up to {sum} bytes but `size` reports a total of \
+                         {} bytes",
+                        table.total
+                    );
+                }
+
+                if let Some(segments) = &options.segments {
+                    for section in &mut table.sections {
+                        let lma = segments
+                            .iter()
+                            .find_map(|segment| segment.lma(section.addr));
+                        section.lma = Some(lma.unwrap_or(section.addr));
+                    }
+                }
+
+                if options.group_by_prefix {
+                    let mut groups: Vec<Section<'_>> = vec![];
+                    for section in table.sections {
+                        let prefix = section.prefix();
+                        match groups.iter_mut().find(|group| group.name == prefix) {
+                            Some(group) => {
+                                group.size += section.size;
+                                group.addr = group.addr.min(section.addr);
+                                group.lma = group.lma.zip(section.lma).map(|(a, b)| a.min(b));
+                            }
+                            None => groups.push(Section {
+                                name: prefix,
+                                ..section
+                            }),
+                        }
+                    }
+                    table.sections = groups;
+                }
+
+                table.human = options.human;
+                match options.sort {
+                    Some(SectionSort::Size) => {
+                        table.sections.sort_by_key(|section| Reverse(section.size));
+                    }
+                    Some(SectionSort::Name) => table.sections.sort_by_key(|section| section.name),
+                    Some(SectionSort::Vma) => table.sections.sort_by_key(|section| section.addr),
+                    None => {}
+                }
+
+                table.render(&mut s);
+                i += len;
+            } else if let Some(rows) = options
+                .human
+                .then(|| BerkeleyRow::parse_table(&lines[i..]))
+                .flatten()
+            {
+                render_berkeley_human(&rows, &mut s);
+                i += rows.len() + 1;
+            } else {
+                s.push_str(lines[i]);
+                s.push('\n');
+                i += 1;
+            }
+        }
 
         s.into_bytes().into()
     } else {
         bytes.into()
     }
 }
+
+// This pass turns the System V (`size -A`) table of a single object file into a JSON object with
+// the size and address of its sections and their Berkeley (`text`, `data` and `bss`) totals. The
+// totals are computed from the ELF `sections`, they are `null` without them.
+pub fn size_json(
+    bytes: &[u8],
+    sections: Option<&[elf::Section]>,
+    totals_only: bool,
+) -> Result<Vec<u8>> {
+    let lines = str::from_utf8(bytes)?.lines().collect::<Vec<_>>();
+    let mut tables = (0..lines.len()).filter_map(|i| SysvTable::parse(&lines[i..]));
+    let (Some((table, _)), None) = (tables.next(), tables.next()) else {
+        bail!("`--format json` only supports artifacts with a single object file");
+    };
+
+    if totals_only {
+        let json = serde_json::json!({
+            "schema_version": crate::JSON_SCHEMA_VERSION,
+            "total": table.total,
+        });
+
+        let mut s = serde_json::to_string_pretty(&json)?;
+        s.push('\n');
+        return Ok(s.into_bytes());
+    }
+
+    let mut totals = sections.map(|_| [0u64; 3]);
+    let mut json_sections = serde_json::Map::new();
+    for section in &table.sections {
+        let berkeley = sections
+            .and_then(|sections| sections.iter().find(|s| s.name == section.name))
+            .and_then(elf::Section::berkeley);
+        if let (Some(totals), Some(berkeley)) = (&mut totals, berkeley) {
+            totals[berkeley as usize] += section.size;
+        }
+
+        json_sections.insert(
+            section.name.to_owned(),
+            serde_json::json!({
+                "size": section.size,
+                "addr": section.addr,
+            }),
+        );
+    }
+
+    let json = serde_json::json!({
+        "schema_version": crate::JSON_SCHEMA_VERSION,
+        "text": totals.map(|totals| totals[elf::Berkeley::Text as usize]),
+        "data": totals.map(|totals| totals[elf::Berkeley::Data as usize]),
+        "bss": totals.map(|totals| totals[elf::Berkeley::Bss as usize]),
+        "total": table.total,
+        "sections": json_sections,
+    });
+
+    let mut s = serde_json::to_string_pretty(&json)?;
+    s.push('\n');
+    Ok(s.into_bytes())
+}
+
+// This pass tells how much of the memory `regions` the loadable sections of `elf` use. A section
+// counts towards the regions of both its virtual and its load address, e.g. `.data` uses both RAM
+// and FLASH.
+pub fn regions(elf: &elf::Elf, regions: &[memory::Region], human: bool) -> String {
+    let mut used = vec![0u64; regions.len()];
+    let region = |addr| regions.iter().position(|region| region.contains(addr));
+    for section in &elf.sections {
+        let Some(berkeley) = section.berkeley() else {
+            continue;
+        };
+
+        let vma = section.addr;
+        let lma = elf
+            .segments
+            .iter()
+            .find_map(|segment| segment.lma(vma))
+            .unwrap_or(vma);
+
+        let vma_region = region(vma);
+        if let Some(i) = vma_region {
+            used[i] += section.size;
+        }
+        // `.bss` only exists at run time
+        if berkeley != elf::Berkeley::Bss {
+            if let Some(i) = region(lma).filter(|&i| Some(i) != vma_region) {
+                used[i] += section.size;
+            }
+        }
+    }
+
+    let mut rows = vec![vec![
+        "region".to_owned(),
+        "used".to_owned(),
+        "size".to_owned(),
+        "usage".to_owned(),
+    ]];
+    for (region, &used) in regions.iter().zip(&used) {
+        let usage = if region.length == 0 {
+            0.0
+        } else {
+            used as f64 * 100.0 / region.length as f64
+        };
+        rows.push(vec![
+            region.name.clone(),
+            size_cell(Radix::Decimal, used, human),
+            size_cell(Radix::Decimal, region.length, human),
+            format!("{usage:.2}%"),
+        ]);
+    }
+
+    render_table(&rows)
+}
+
+// This pass compares the `size -A` output of an artifact, `current`, with the one of a `baseline`
+// artifact, section by section. Sections missing from one of the artifacts count as empty there.
+pub fn size_diff(current: &[u8], baseline: &[u8], all: bool) -> Result<String> {
+    fn table(bytes: &[u8]) -> Result<SysvTable<'_>> {
+        let lines = str::from_utf8(bytes)?.lines().collect::<Vec<_>>();
+        let mut tables = (0..lines.len()).filter_map(|i| SysvTable::parse(&lines[i..]));
+        match (tables.next(), tables.next()) {
+            (Some((table, _)), None) => Ok(table),
+            _ => bail!("`--diff` only supports artifacts with a single object file"),
+        }
+    }
+
+    let current = table(current)?;
+    let baseline = table(baseline)?;
+
+    let size = |table: &SysvTable<'_>, name| {
+        table
+            .sections
+            .iter()
+            .filter(|section| section.name == name)
+            .map(|section| section.size)
+            .sum::<u64>()
+    };
+
+    // The sections of the current artifact in order, followed by the ones that were removed
+    let mut names = current
+        .sections
+        .iter()
+        .map(|section| section.name)
+        .collect::<Vec<_>>();
+    for section in &baseline.sections {
+        if !names.contains(&section.name) {
+            names.push(section.name);
+        }
+    }
+
+    let mut rows = vec![["section", "baseline", "current", "delta"].map(str::to_owned)];
+    let mut row = |name: &str, baseline: u64, current: u64| {
+        rows.push([
+            name.to_owned(),
+            baseline.to_string(),
+            current.to_string(),
+            delta(baseline, current),
+        ])
+    };
+    for name in names {
+        let (baseline, current) = (size(&baseline, name), size(&current, name));
+        if all || baseline != current {
+            row(name, baseline, current);
+        }
+    }
+    row("Total", baseline.total, current.total);
+
+    Ok(render_table(&rows))
+}
+
+// `+16`, `-16` or `0`
+fn delta(baseline: u64, current: u64) -> String {
+    if current >= baseline {
+        match current - baseline {
+            0 => "0".to_owned(),
+            n => format!("+{n}"),
+        }
+    } else {
+        format!("-{}", baseline - current)
+    }
+}
+
+// Left aligns the first column and right aligns the others
+fn render_table<R: AsRef<[String]>>(rows: &[R]) -> String {
+    let mut widths = vec![0; rows.first().map_or(0, |row| row.as_ref().len())];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row.as_ref()) {
+            *width = (*width).max(cell.len() + 2);
+        }
+    }
+
+    let mut s = String::new();
+    for row in rows {
+        let mut cells = row.as_ref().iter().zip(&widths);
+        if let Some((name, width)) = cells.next() {
+            s.push_str(&format!("{name:<width$}"));
+        }
+        for (cell, width) in cells {
+            s.push_str(&format!(" {cell:>width$}"));
+        }
+        s.push('\n');
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nm_options(format: NmFormat, sort: &[SymbolSort], reverse: bool) -> NmOptions {
+        NmOptions {
+            demangle: false,
+            names_only: false,
+            symbols: None,
+            format,
+            sort: sort.to_vec(),
+            reverse,
+        }
+    }
+
+    #[test]
+    fn parses_and_renders_sysv_tables() {
+        let lines = [
+            "app  :",
+            "section     size        addr",
+            ".vector_table   1024   134217728",
+            ".text     512   134218752",
+            "Total     1536",
+            "",
+        ];
+        assert!(SysvTable::parse(&lines).is_none());
+
+        let (table, len) = SysvTable::parse(&lines[1..]).unwrap();
+        assert_eq!(len, 4);
+        assert_eq!(table.total, 1536);
+        assert_eq!(table.sections[1].name, ".text");
+        assert_eq!(table.sections[1].addr, 0x0800_0400);
+
+        let mut s = String::new();
+        table.render(&mut s);
+        assert_eq!(
+            s,
+            "\
+section           size        addr
+.vector_table     1024   0x8000000
+.text              512   0x8000400
+Total             1536
+"
+        );
+    }
+
+    #[test]
+    fn parses_sysv_tables_in_any_radix() {
+        let hex = ["section size addr", ".text 0x200 0x8000400", "Total 0x200"];
+        let (table, _) = SysvTable::parse(&hex).unwrap();
+        assert_eq!((table.sections[0].size, table.total), (512, 512));
+
+        let octal = [
+            "section size addr",
+            ".text 01000 01000002000",
+            "Total 01000",
+        ];
+        let (table, _) = SysvTable::parse(&octal).unwrap();
+        assert_eq!(table.sections[0].addr, 0x0800_0400);
+
+        let truncated = ["section size addr", ".text 512 0"];
+        assert!(SysvTable::parse(&truncated).is_none());
+    }
+
+    #[test]
+    fn renders_the_lma_column() {
+        let lines = [
+            "section size addr",
+            ".text 512 134217728",
+            ".data 16 536870912",
+            "Total 528",
+        ];
+        let (mut table, _) = SysvTable::parse(&lines).unwrap();
+        table.sections[0].lma = Some(0x0800_0000);
+        table.sections[1].lma = Some(0x0800_0200);
+
+        let mut s = String::new();
+        table.render(&mut s);
+        assert_eq!(
+            s,
+            "\
+section     size         addr         lma
+.text        512    0x8000000   0x8000000
+.data         16   0x20000000   0x8000200
+Total        528
+"
+        );
+    }
+
+    #[test]
+    fn readobj_only_demangles_the_name_column() {
+        let input = "\
+Symbol table '.symtab' contains 2 entries:
+   Num:    Value  Size Type    Bind   Vis       Ndx Name
+     0: 00000000     0 NOTYPE  LOCAL  DEFAULT   UND
+     1: 08000400     8 FUNC    GLOBAL DEFAULT     2 _ZN3foo3barE
+     2: 08000408     4 FUNC    GLOBAL DEFAULT [<other>: 0x88]     2 _ZN3foo3bazE
+
+_ZN3foo3quxE
+";
+        let output = "\
+Symbol table '.symtab' contains 2 entries:
+   Num:    Value  Size Type    Bind   Vis       Ndx Name
+     0: 00000000     0 NOTYPE  LOCAL  DEFAULT   UND
+     1: 08000400     8 FUNC    GLOBAL DEFAULT     2 foo::bar
+     2: 08000408     4 FUNC    GLOBAL DEFAULT [<other>: 0x88]     2 foo::baz
+
+foo::qux
+";
+        assert_eq!(str::from_utf8(&readobj(input.as_bytes())).unwrap(), output);
+        assert_eq!(readobj(b"\xff_ZN3foo3barE"), &b"\xff_ZN3foo3barE"[..]);
+    }
+
+    #[test]
+    fn demangles_wide_lines() {
+        // `--wide` keeps the whole instruction, and its symbolic operands, on one line
+        let bytes = "00 ".repeat(4096);
+        let line = format!(
+            "8000400: {bytes} bl <_ZN3app4main17h0123456789abcdefE+0x8> ; _ZN3app4init17h0123456789abcdefE\n"
+        );
+        let demangled = format!(
+            "8000400: {bytes} bl <app::main::h0123456789abcdef+0x8> ; app::init::h0123456789abcdef\n"
+        );
+
+        assert_eq!(&*demangle(line.as_bytes()), demangled.as_bytes());
+    }
+
+    #[test]
+    fn size_json_schema_version() {
+        let sysv = b"section size addr\n.text 512 0\nTotal 512\n";
+        for totals_only in [false, true] {
+            let json = size_json(sysv, None, totals_only).unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+            assert_eq!(json["schema_version"], crate::JSON_SCHEMA_VERSION);
+        }
+    }
+
+    #[test]
+    fn size_json_of_a_firmware() {
+        let sysv = b"target/thumbv7em-none-eabihf/release/app  :
+section             size        addr
+.vector_table       1024   134217728
+.text               6896   134218752
+.rodata              980   134225648
+.data                 48   536870912
+.bss                 612   536870960
+.uninit                0   536871572
+.comment             147           0
+Total               9707
+";
+        // (name, sh_type, sh_flags): PROGBITS or NOBITS, and WRITE | ALLOC | EXECINSTR
+        let sections = [
+            (".vector_table", 1, 0x2),
+            (".text", 1, 0x6),
+            (".rodata", 1, 0x2),
+            (".data", 1, 0x3),
+            (".bss", 8, 0x3),
+            (".uninit", 8, 0x3),
+            (".comment", 1, 0x30),
+        ]
+        .map(|(name, kind, flags)| elf::Section {
+            name: name.to_owned(),
+            kind,
+            flags,
+            addr: 0,
+            size: 0,
+        });
+
+        let json = size_json(sysv, Some(&sections), false).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "schema_version": crate::JSON_SCHEMA_VERSION,
+                "text": 8900,
+                "data": 48,
+                "bss": 612,
+                "total": 9707,
+                "sections": {
+                    ".vector_table": { "size": 1024, "addr": 134217728 },
+                    ".text": { "size": 6896, "addr": 134218752 },
+                    ".rodata": { "size": 980, "addr": 134225648 },
+                    ".data": { "size": 48, "addr": 536870912 },
+                    ".bss": { "size": 612, "addr": 536870960 },
+                    ".uninit": { "size": 0, "addr": 536871572 },
+                    ".comment": { "size": 147, "addr": 0 },
+                },
+            })
+        );
+
+        // Without the ELF sections, e.g. of an archive, the Berkeley totals are unknown
+        let json = size_json(sysv, None, false).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["text"], serde_json::Value::Null);
+        assert_eq!(json["total"], 9707);
+    }
+
+    #[test]
+    fn demangles_legacy_and_v0_symbols() {
+        assert_eq!(demangle_str("_ZN3foo3barE"), "foo::bar");
+        assert_eq!(
+            demangle_str("call _RNvCs1234_3foo3bar"),
+            "call foo[3c1c0]::bar"
+        );
+        assert_eq!(
+            demangle_str("_RNvCs1234_3foo3bar _ZN3foo3bazE"),
+            "foo[3c1c0]::bar foo::baz"
+        );
+        assert!(matches!(demangle_str("main"), Cow::Borrowed("main")));
+    }
+
+    #[test]
+    fn nm_rows() {
+        let row = |format: NmFormat, line| {
+            format
+                .row(line)
+                .map(|row| (row.name.to_owned(), row.address, row.size))
+        };
+        let some = |name: &str, address, size| Some((name.to_owned(), address, size));
+
+        assert_eq!(
+            row(NmFormat::Bsd, "08000400 T main"),
+            some("main", Some(0x0800_0400), None)
+        );
+        assert_eq!(
+            row(
+                NmFormat::Bsd,
+                "08000400 00000008 T main\t/app/src/main.rs:3"
+            ),
+            some("main", Some(0x0800_0400), Some(8))
+        );
+        assert_eq!(
+            row(NmFormat::Bsd, "         U memcpy"),
+            some("memcpy", None, None)
+        );
+        assert_eq!(row(NmFormat::JustSymbols, "main"), some("main", None, None));
+        assert_eq!(
+            row(NmFormat::Posix, "main T 8000400 8"),
+            some("main", Some(0x0800_0400), Some(8))
+        );
+        assert_eq!(row(NmFormat::Posix, "memcpy U"), some("memcpy", None, None));
+        assert_eq!(
+            row(
+                NmFormat::Sysv,
+                "main                |08000400|   T  |            FUNC|00000008|     |.text"
+            ),
+            some("main", Some(0x0800_0400), Some(8))
+        );
+
+        // Headers
+        assert_eq!(row(NmFormat::Bsd, "libapp.a(app.o):"), None);
+        assert_eq!(row(NmFormat::Posix, "libapp.a[app.o]:"), None);
+        assert_eq!(
+            row(
+                NmFormat::Sysv,
+                "Name                  Value   Class        Type"
+            ),
+            None
+        );
+        assert_eq!(row(NmFormat::JustSymbols, ""), None);
+    }
+
+    #[test]
+    fn nm_format_from_args() {
+        let format = |args: &[&str]| match NmFormat::from_args(NmFormat::Bsd, args) {
+            NmFormat::Bsd => "bsd",
+            NmFormat::Darwin => "darwin",
+            NmFormat::JustSymbols => "just-symbols",
+            NmFormat::Posix => "posix",
+            NmFormat::Sysv => "sysv",
+        };
+
+        assert_eq!(format(&[]), "bsd");
+        assert_eq!(format(&["-P"]), "posix");
+        assert_eq!(format(&["--portability"]), "posix");
+        assert_eq!(format(&["-f", "sysv"]), "sysv");
+        assert_eq!(format(&["--format=darwin"]), "darwin");
+        assert_eq!(format(&["-fposix", "-j"]), "just-symbols");
+        // The last one wins
+        assert_eq!(format(&["-P", "--format", "bsd"]), "bsd");
+        assert_eq!(format(&["--format=unknown"]), "bsd");
+    }
+
+    #[test]
+    fn nm_no_demangle() {
+        let input =
+            b"08000400 T _ZN3app4main17h0123456789abcdefE\n         U _RNvCs1234_3foo3bar\n";
+
+        let options = nm_options(NmFormat::Bsd, &[], false);
+        assert!(matches!(nm(input, &options), Cow::Borrowed(output) if output == input));
+        let options = nm_options(NmFormat::Bsd, &[SymbolSort::Name], false);
+        assert_eq!(
+            &*nm(input, &options),
+            b"         U _RNvCs1234_3foo3bar\n08000400 T _ZN3app4main17h0123456789abcdefE\n"
+        );
+
+        let options = NmOptions {
+            demangle: true,
+            ..nm_options(NmFormat::Bsd, &[], false)
+        };
+        assert_eq!(
+            &*nm(input, &options),
+            b"08000400 T app::main::h0123456789abcdef\n         U foo[3c1c0]::bar\n"
+        );
+    }
+
+    #[test]
+    fn demangling_twice_changes_nothing() {
+        let input = "\
+08000400 <_ZN3app4main17h0123456789abcdefE>:
+ 8000400: bl <_RNvCs1234_3foo3bar+0x4>
+ 8000404: b <_ZN4core9panicking5panic17h0123456789abcdefE>
+";
+        let once = demangle(input.as_bytes()).into_owned();
+        assert_ne!(once, input.as_bytes());
+        assert!(matches!(demangle(&once), Cow::Borrowed(twice) if twice == once));
+    }
+
+    #[test]
+    fn demangles_large_repeating_inputs() {
+        let mangled = ["_ZN3foo3barE", "_ZN3foo3bazE", "_RNvCs1234_3foo3bar"];
+        let demangled = ["foo::bar", "foo::baz", "foo[3c1c0]::bar"];
+        let lines = |symbols: &[&str]| {
+            (0..30_000)
+                .map(|i| format!(" 8000{i:04x}: bl <{}+0x{i:x}>\n", symbols[i % 3]))
+                .collect::<String>()
+        };
+
+        let output = demangle(lines(&mangled).as_bytes()).into_owned();
+
+        assert_eq!(String::from_utf8(output).unwrap(), lines(&demangled));
+    }
+
+    #[test]
+    fn demangles_the_symbols_of_operands() {
+        assert_eq!(
+            demangle_str("  jal ra, <_ZN3foo3barE+0x8>"),
+            "  jal ra, <foo::bar+0x8>"
+        );
+        assert_eq!(
+            demangle_str("bl 0x8000100 <_ZN4core9panicking5panic17h0123456789abcdefE+0x12>"),
+            "bl 0x8000100 <core::panicking::panic::h0123456789abcdef+0x12>"
+        );
+        assert_eq!(
+            demangle_str("  jal ra, <_RNvCs1234_3foo3bar+0x4>"),
+            "  jal ra, <foo[3c1c0]::bar+0x4>"
+        );
+    }
+
+    #[test]
+    fn human_sizes() {
+        assert_eq!(human_size(1023), "1023 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(1024 * 1024 - 1), "1.0 MiB");
+        assert_eq!(human_size(u64::MAX), "16.0 EiB");
+    }
+
+    #[test]
+    fn diffs_sizes() {
+        let baseline = b"\
+section size addr
+.text 512 0
+.rodata 64 512
+.data 8 576
+Total 584
+";
+        let current = b"\
+section size addr
+.text 528 0
+.bss 16 528
+.data 8 544
+Total 552
+";
+        assert_eq!(
+            size_diff(current, baseline, false).unwrap(),
+            "\
+section     baseline   current   delta
+.text            512       528     +16
+.bss               0        16     +16
+.rodata           64         0     -64
+Total            584       552     -32
+"
+        );
+        assert!(size_diff(current, baseline, true)
+            .unwrap()
+            .contains("\n.data              8         8       0\n"));
+        assert!(size_diff(b"", baseline, false).is_err());
+    }
+
+    #[test]
+    fn sorts_symbols() {
+        let input = "\
+00000010 T b
+00000008 T c
+         U a
+";
+        let bsd = |sort: &[SymbolSort], reverse| {
+            sort_symbols(input, &nm_options(NmFormat::Bsd, sort, reverse))
+        };
+
+        assert_eq!(
+            bsd(&[SymbolSort::Name], false),
+            "         U a\n00000010 T b\n00000008 T c\n"
+        );
+        assert_eq!(
+            bsd(&[SymbolSort::Address], false),
+            "         U a\n00000008 T c\n00000010 T b\n"
+        );
+        assert_eq!(
+            bsd(&[SymbolSort::Address], true),
+            "00000010 T b\n00000008 T c\n         U a\n"
+        );
+    }
+
+    #[test]
+    fn sorts_the_symbols_of_each_file_separately() {
+        let input = "\
+a.o:
+00000000 00000004 T z
+00000004 00000008 T y
+
+b.o:
+00000000 00000002 T x
+";
+        let output = "\
+a.o:
+00000004 00000008 T y
+00000000 00000004 T z
+
+b.o:
+00000000 00000002 T x
+";
+        let options = nm_options(NmFormat::Bsd, &[SymbolSort::Size], true);
+        assert_eq!(sort_symbols(input, &options), output);
+    }
+}
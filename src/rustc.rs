@@ -1,9 +1,10 @@
-use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::{env, fs};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 
+/// The sysroot of the toolchain, as reported by `$RUSTC --print sysroot`
 pub fn sysroot() -> Result<String> {
     let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
     let output = Command::new(rustc).arg("--print").arg("sysroot").output()?;
@@ -11,6 +12,7 @@ pub fn sysroot() -> Result<String> {
     Ok(String::from_utf8(output.stdout)?.trim().to_owned())
 }
 
+/// The directory of the sysroot where the `llvm-tools` component installs the tools
 // See: https://github.com/rust-lang/rust/blob/564758c4c329e89722454dd2fbb35f1ac0b8b47c/src/bootstrap/dist.rs#L2334-L2341
 pub fn rustlib() -> Result<PathBuf> {
     let sysroot = sysroot()?;
@@ -21,3 +23,99 @@ pub fn rustlib() -> Result<PathBuf> {
     pathbuf.push("bin");
     Ok(pathbuf)
 }
+
+/// Locates the JSON spec of a custom target, which is either given as the path of the spec or, like
+/// rustc does, looked up as `$target.json` in the directories of `RUST_TARGET_PATH` if no built-in
+/// target has that name
+pub fn target_spec(target: &str) -> Result<Option<PathBuf>> {
+    if target.ends_with(".json") {
+        return Ok(Some(PathBuf::from(target)));
+    }
+
+    let Some(dirs) = env::var_os("RUST_TARGET_PATH") else {
+        return Ok(None);
+    };
+    if target_list()?.iter().any(|builtin| builtin == target) {
+        return Ok(None);
+    }
+
+    Ok(env::split_paths(&dirs)
+        .map(|dir| dir.join(format!("{target}.json")))
+        .find(|spec| spec.is_file()))
+}
+
+/// The built-in targets, as reported by `$RUSTC --print target-list`
+fn target_list() -> Result<Vec<String>> {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc)
+        .args(["--print", "target-list"])
+        .output()?;
+    if !output.status.success() {
+        bail!("`rustc --print target-list` failed");
+    }
+
+    Ok(String::from_utf8(output.stdout)?
+        .lines()
+        .map(str::to_owned)
+        .collect())
+}
+
+/// The fields of a custom target spec that `--print cfg` would report
+pub struct TargetSpec {
+    pub arch: String,
+    pub endian: String,
+    pub llvm_target: String,
+}
+
+impl TargetSpec {
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let json: serde_json::Value = serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+        let field = |name: &str| json.get(name).and_then(|value| value.as_str());
+        let required = |name: &str| {
+            field(name)
+                .map(|value| value.to_owned())
+                .with_context(|| format!("The target spec {} has no `{name}`", path.display()))
+        };
+
+        Ok(TargetSpec {
+            arch: required("arch")?,
+            endian: field("target-endian").unwrap_or("little").to_owned(),
+            llvm_target: required("llvm-target")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_targets_take_precedence_over_rust_target_path() {
+        let dir = env::temp_dir().join("cargo-binutils-rust-target-path");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("thumbv7m-none-eabi.json"), "{}").unwrap();
+        fs::write(dir.join("my-board.json"), "{}").unwrap();
+
+        // No other test sets it
+        env::set_var("RUST_TARGET_PATH", &dir);
+        let builtin = target_spec("thumbv7m-none-eabi").unwrap();
+        let custom = target_spec("my-board").unwrap();
+        let unknown = target_spec("other-board").unwrap();
+        env::remove_var("RUST_TARGET_PATH");
+
+        assert_eq!(builtin, None);
+        assert_eq!(custom, Some(dir.join("my-board.json")));
+        assert_eq!(unknown, None);
+        assert_eq!(
+            target_spec("specs/board.json").unwrap(),
+            Some(PathBuf::from("specs/board.json"))
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,97 @@
+use std::path::Path;
+use std::process::Stdio;
+use std::str;
+
+use anyhow::{bail, Result};
+
+use crate::Tool;
+
+/// A symbol of an artifact's symbol table
+pub struct Symbol {
+    /// The (possibly mangled) name as it appears in the symbol table
+    pub name: String,
+    pub address: u64,
+}
+
+impl Symbol {
+    /// The demangled name, without the trailing hash of legacy mangled Rust symbols
+    pub fn demangled(&self) -> String {
+        format!("{:#}", rustc_demangle::demangle(&self.name))
+    }
+}
+
+/// Reads the symbol table of `file` using `nm`
+pub fn read(file: &Path) -> Result<Vec<Symbol>> {
+    let output = crate::tool_command(Tool::Nm)?
+        .args(["--format=bsd", "--defined-only"])
+        .arg(file)
+        .stderr(Stdio::inherit())
+        .output()?;
+
+    if !output.status.success() {
+        bail!("Failed to read the symbols of {}", file.display());
+    }
+
+    Ok(str::from_utf8(&output.stdout)?
+        .lines()
+        .filter_map(|line| {
+            // `$address $type $name`
+            let mut fields = line.split_whitespace();
+            let address = u64::from_str_radix(fields.next()?, 16).ok()?;
+            let name = fields.last()?;
+
+            Some(Symbol {
+                name: name.to_owned(),
+                address,
+            })
+        })
+        .collect())
+}
+
+/// Finds the symbol called `name`, which can be either the mangled or the demangled name,
+/// erroring with some near matches if there's no such symbol
+pub fn resolve<'s>(symbols: &'s [Symbol], name: &str) -> Result<&'s Symbol> {
+    let mut matching = symbols
+        .iter()
+        .filter(|symbol| {
+            let demangled = rustc_demangle::demangle(&symbol.name);
+            symbol.name == name || format!("{demangled:#}") == name || demangled.to_string() == name
+        })
+        .collect::<Vec<_>>();
+    // The objects of an archive, e.g. a staticlib, can each define the same (weak) symbol
+    matching.sort_by(|a, b| a.name.cmp(&b.name));
+    matching.dedup_by(|a, b| a.name == b.name);
+
+    match *matching {
+        [symbol] => Ok(symbol),
+        [] => {
+            let needle = name.to_lowercase();
+            let near_matches = symbols
+                .iter()
+                .map(Symbol::demangled)
+                .filter(|demangled| demangled.to_lowercase().contains(&needle))
+                .take(5)
+                .collect::<Vec<_>>();
+
+            if near_matches.is_empty() {
+                bail!("Could not find symbol `{name}`");
+            }
+
+            bail!(
+                "Could not find symbol `{name}`, did you mean one of: {}",
+                near_matches.join(", ")
+            )
+        }
+        _ => {
+            let candidates = matching
+                .iter()
+                .map(|symbol| symbol.name.as_str())
+                .collect::<Vec<_>>();
+
+            bail!(
+                "Symbol `{name}` is ambiguous, it matches: {}",
+                candidates.join(", ")
+            )
+        }
+    }
+}
@@ -1,41 +1,101 @@
 use std::env::consts::EXE_SUFFIX;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::{env, process};
+use std::str::FromStr;
+use std::{env, fmt, fs, process};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use rustc_version::Version;
 
 use crate::rustc::rustlib;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Tool {
+    Addr2line,
     Ar,
     As,
     Cov,
+    Cxxfilt,
     Lld,
+    Mca,
     Nm,
     Objcopy,
     Objdump,
     Profdata,
+    Ranlib,
     Readobj,
     Size,
+    Strings,
     Strip,
+    Symbolizer,
 }
 
 impl Tool {
-    pub fn name(self) -> &'static str {
+    /// All the tools, in alphabetical order
+    pub const fn all() -> &'static [Tool] {
+        &[
+            Tool::Addr2line,
+            Tool::Ar,
+            Tool::As,
+            Tool::Cov,
+            Tool::Cxxfilt,
+            Tool::Lld,
+            Tool::Mca,
+            Tool::Nm,
+            Tool::Objcopy,
+            Tool::Objdump,
+            Tool::Profdata,
+            Tool::Ranlib,
+            Tool::Readobj,
+            Tool::Size,
+            Tool::Strings,
+            Tool::Strip,
+            Tool::Symbolizer,
+        ]
+    }
+
+    pub const fn name(self) -> &'static str {
         match self {
+            Tool::Addr2line => "addr2line",
             Tool::Ar => "ar",
             Tool::As => "as",
             Tool::Cov => "cov",
+            Tool::Cxxfilt => "cxxfilt",
             Tool::Lld => "lld",
+            Tool::Mca => "mca",
             Tool::Nm => "nm",
             Tool::Objcopy => "objcopy",
             Tool::Objdump => "objdump",
             Tool::Profdata => "profdata",
+            Tool::Ranlib => "ranlib",
             Tool::Readobj => "readobj",
             Tool::Size => "size",
+            Tool::Strings => "strings",
             Tool::Strip => "strip",
+            Tool::Symbolizer => "symbolizer",
+        }
+    }
+
+    /// One line summary of what the tool does
+    pub const fn description(self) -> &'static str {
+        match self {
+            Tool::Addr2line => "Translates addresses into file names and line numbers",
+            Tool::Ar => "Creates, modifies and extracts from archives",
+            Tool::As => "Assembles source files",
+            Tool::Cov => "Reports and shows code coverage",
+            Tool::Cxxfilt => "Demangles symbol names",
+            Tool::Lld => "Links object files",
+            Tool::Mca => "Analyzes the performance of machine code",
+            Tool::Nm => "Lists the symbols of object files",
+            Tool::Objcopy => "Copies and translates object files",
+            Tool::Objdump => "Disassembles and dumps the contents of object files",
+            Tool::Profdata => "Merges and shows profile data",
+            Tool::Ranlib => "Generates the symbol index of archives",
+            Tool::Readobj => "Displays low level information about object files",
+            Tool::Size => "Prints the size of the sections of object files",
+            Tool::Strings => "Prints the printable strings of binary files",
+            Tool::Strip => "Removes symbols and debug info from object files",
+            Tool::Symbolizer => "Symbolizes addresses into source locations",
         }
     }
 
@@ -46,32 +106,79 @@ impl Tool {
         }
     }
 
+    /// The path of the tool in the toolchain, which doesn't exist if the tool isn't installed
     pub fn path(self) -> Result<PathBuf> {
-        let mut path = rustlib()?;
-        path.push(self.exe());
+        let rustlib = rustlib()?;
+        let path = rustlib.join(self.exe());
+        if path.exists() {
+            Ok(path)
+        } else {
+            Ok(self.versioned_exe(&rustlib).unwrap_or(path))
+        }
+    }
+
+    /// Like `path` but errors, with a hint on how to install the tool, if there's no such file
+    pub fn resolve(self) -> Result<PathBuf> {
+        let path = self.path()?;
+        if !path.exists() {
+            bail!("{}", self.not_found(&path));
+        }
+
         Ok(path)
     }
 
+    // The error about the tool missing at `path`
+    fn not_found(self, path: &Path) -> String {
+        format!(
+            "Could not find tool: {}\nat: {}\n{}",
+            self.name(),
+            path.display(),
+            missing_tool_hint()
+        )
+    }
+
+    /// Looks for a version suffixed executable of the tool in `dir`, like the `llvm-objdump-17`
+    /// some distributions ship. If there are several the one with the highest version is picked.
+    fn versioned_exe(self, dir: &Path) -> Option<PathBuf> {
+        let exe = self.exe();
+        let stem = exe.strip_suffix(EXE_SUFFIX).unwrap_or(&exe);
+
+        fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let file_name = entry.file_name().into_string().ok()?;
+                let version = file_name
+                    .strip_suffix(EXE_SUFFIX)?
+                    .strip_prefix(stem)?
+                    .strip_prefix('-')?
+                    .split('.')
+                    .map(|n| n.parse::<u32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .ok()?;
+
+                Some((version, entry.path()))
+            })
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, path)| path)
+    }
+
     /// Forwards execution to the specified tool.
-    /// If the tool fails to start or is not found this process exits with
-    /// status code 101 the same as if the process has a panic!
+    /// If the tool is not found this process exits with status code 102. If the toolchain can't
+    /// be queried or the tool fails to start it exits with status code 101, the same as if the
+    /// process has a panic!
     pub fn rust_exec(self) -> ! {
         let path = match self.path() {
+            Ok(path) => path,
             Err(e) => {
-                eprintln!("Failed to find tool: {}\n{}", self.name(), e);
+                eprintln!("{e}");
                 process::exit(101)
             }
-            Ok(p) => p,
         };
-
         if !path.exists() {
-            eprintln!(
-                "Could not find tool: {}\nat: {}\nConsider `rustup component add llvm-tools`",
-                self.name(),
-                path.to_string_lossy()
-            );
+            eprintln!("{}", self.not_found(&path));
             process::exit(102)
-        };
+        }
 
         // Note: The first argument is the name of the binary (e.g. `rust-nm`)
         let args = env::args().skip(1);
@@ -105,13 +212,181 @@ impl Tool {
         }
     }
 
+    // Whether `cargo $tool` accepts the `cargo build` flags. `cargo cov` only builds the project
+    // for some of its convenience flags.
+    pub fn can_build(self) -> bool {
+        self.needs_build() || self == Tool::Cov
+    }
+
     // Whether this tool requires the project to be previously built
     pub fn needs_build(self) -> bool {
         match self {
-            Tool::Ar | Tool::As | Tool::Cov | Tool::Lld | Tool::Profdata => false,
-            Tool::Nm | Tool::Objcopy | Tool::Objdump | Tool::Readobj | Tool::Size | Tool::Strip => {
-                true
-            }
+            Tool::Ar
+            | Tool::As
+            | Tool::Cov
+            | Tool::Cxxfilt
+            | Tool::Lld
+            | Tool::Mca
+            | Tool::Profdata
+            | Tool::Ranlib => false,
+            Tool::Addr2line
+            | Tool::Nm
+            | Tool::Objcopy
+            | Tool::Objdump
+            | Tool::Readobj
+            | Tool::Size
+            | Tool::Strings
+            | Tool::Strip
+            | Tool::Symbolizer => true,
+        }
+    }
+}
+
+impl FromStr for Tool {
+    type Err = anyhow::Error;
+
+    /// Parses the name of a tool, e.g. `objdump`, or of its executable, e.g. `rust-objdump` or
+    /// `llvm-objdump`
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.strip_suffix(EXE_SUFFIX).unwrap_or(s);
+        let name = name
+            .strip_prefix("rust-")
+            .or_else(|| name.strip_prefix("llvm-"))
+            .unwrap_or(name);
+
+        match Tool::all().iter().find(|tool| tool.name() == name) {
+            Some(&tool) => Ok(tool),
+            None => bail!("Unknown tool `{s}`"),
         }
     }
 }
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Suggests how to get a missing tool, based on the components rustup reports as installed
+pub(crate) fn missing_tool_hint() -> String {
+    let output = Command::new("rustup")
+        .args(["component", "list", "--installed"])
+        .output();
+
+    let installed = match output {
+        Ok(output) if output.status.success() => Some(
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .any(|component| component.starts_with("llvm-tools")),
+        ),
+        // Not managed by rustup
+        _ => None,
+    };
+
+    let version = rustc_version::version_meta().ok().map(|meta| meta.semver);
+    component_hint(llvm_tools_component(version.as_ref()), installed)
+}
+
+/// The name of the component with the LLVM tools, which dropped its `-preview` suffix in 1.77
+fn llvm_tools_component(version: Option<&Version>) -> &'static str {
+    match version {
+        Some(version) if (version.major, version.minor) < (1, 77) => "llvm-tools-preview",
+        _ => "llvm-tools",
+    }
+}
+
+// `installed` tells whether rustup reports the component as installed, if rustup manages the
+// toolchain
+fn component_hint(component: &str, installed: Option<bool>) -> String {
+    match installed {
+        Some(true) => format!(
+            "The `{component}` component is installed but the tool is missing, the toolchain may \
+             be corrupted.\nConsider reinstalling the component with \
+             `rustup component remove {component} && rustup component add {component}`"
+        ),
+        Some(false) => format!("Consider `rustup component add {component}`"),
+        None => "Make sure the LLVM tools are installed alongside your Rust toolchain".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory for each test, as they run in parallel
+    fn rustlib(name: &str, files: &[&str]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("cargo-binutils-rustlib-{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for file in files {
+            fs::write(dir.join(format!("{file}{EXE_SUFFIX}")), "").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn versioned_exe_picks_the_highest_version() {
+        let dir = rustlib(
+            "versioned",
+            &[
+                "llvm-objdump-9",
+                "llvm-objdump-17.0.1",
+                "llvm-objdump-16",
+                "llvm-objdump-new",
+                "llvm-objdumpx-18",
+                "llvm-nm-18",
+            ],
+        );
+
+        assert_eq!(
+            Tool::Objdump.versioned_exe(&dir),
+            Some(dir.join(format!("llvm-objdump-17.0.1{EXE_SUFFIX}")))
+        );
+        assert_eq!(
+            Tool::Nm.versioned_exe(&dir),
+            Some(dir.join(format!("llvm-nm-18{EXE_SUFFIX}")))
+        );
+        assert_eq!(Tool::Size.versioned_exe(&dir), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn versioned_exe_without_a_rustlib() {
+        let dir = env::temp_dir().join("cargo-binutils-rustlib-missing");
+        assert_eq!(Tool::Objdump.versioned_exe(&dir), None);
+    }
+
+    #[test]
+    fn names_roundtrip() {
+        for &tool in Tool::all() {
+            assert_eq!(tool.name().parse::<Tool>().unwrap(), tool);
+            assert_eq!(tool.to_string(), tool.name());
+            assert_eq!(tool.exe().parse::<Tool>().unwrap(), tool);
+            assert_eq!(format!("rust-{tool}").parse::<Tool>().unwrap(), tool);
+        }
+
+        assert_eq!(
+            "objdumb".parse::<Tool>().unwrap_err().to_string(),
+            "Unknown tool `objdumb`"
+        );
+    }
+
+    #[test]
+    fn llvm_tools_components() {
+        let component = |version| llvm_tools_component(Some(&Version::parse(version).unwrap()));
+
+        assert_eq!(component("1.76.0"), "llvm-tools-preview");
+        assert_eq!(component("1.77.0-nightly"), "llvm-tools");
+        assert_eq!(component("1.77.0"), "llvm-tools");
+        assert_eq!(llvm_tools_component(None), "llvm-tools");
+
+        assert_eq!(
+            component_hint("llvm-tools-preview", Some(false)),
+            "Consider `rustup component add llvm-tools-preview`"
+        );
+        assert!(component_hint("llvm-tools", Some(true))
+            .ends_with("`rustup component remove llvm-tools && rustup component add llvm-tools`"));
+        assert!(!component_hint("llvm-tools", None).contains("rustup"));
+    }
+}
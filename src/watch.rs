@@ -0,0 +1,175 @@
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use std::{fs, thread};
+
+use anyhow::Result;
+use cargo_metadata::Metadata;
+use clap::ArgMatches;
+
+use crate::Tool;
+
+// `--watch` polls the modification times of the workspace files rather than relying on a file
+// system notification API, which keeps this free of dependencies and works the same everywhere.
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Runs the subcommand again every time a file of the workspace changes, this never returns
+/// unless writing to `out` fails. The screen is cleared before each run if `out` is a terminal.
+pub fn run(
+    tool: Tool,
+    matches: &ArgMatches,
+    metadata: &Metadata,
+    terminal: bool,
+    out: &mut impl Write,
+) -> Result<i32> {
+    loop {
+        if terminal {
+            // Clear the screen and move the cursor to the top left corner
+            write!(out, "\x1b[2J\x1b[H")?;
+        } else {
+            writeln!(out, "----")?;
+        }
+        out.flush()?;
+
+        // A failed build shouldn't stop the watch, the next change probably fixes it
+        if let Err(e) = crate::run_once(tool, matches.clone(), out) {
+            eprintln!("error: {e}");
+        }
+
+        // Taken after the run as building can write files like `Cargo.lock`
+        let root = metadata.workspace_root.as_std_path();
+        let target_dir = metadata.target_directory.as_std_path();
+        let snapshot = Snapshot::take(root, target_dir);
+        while Snapshot::take(root, target_dir) == snapshot {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// The state of the files of the workspace, other than the ones in the target directory and in
+/// `.git`
+#[derive(Debug, PartialEq, Eq)]
+struct Snapshot {
+    files: usize,
+    modified: Option<SystemTime>,
+}
+
+impl Snapshot {
+    fn take(root: &Path, target_dir: &Path) -> Self {
+        let mut snapshot = Snapshot {
+            files: 0,
+            modified: None,
+        };
+        snapshot.visit(root, target_dir);
+        snapshot
+    }
+
+    fn visit(&mut self, dir: &Path, target_dir: &Path) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            // Other dot directories, like `.cargo`, can hold files that affect the build
+            if path == target_dir || entry.file_name() == ".git" {
+                continue;
+            }
+
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+
+            if metadata.is_dir() {
+                self.visit(&path, target_dir);
+            } else {
+                self.files += 1;
+                self.modified = self.modified.max(metadata.modified().ok());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    // A fresh directory for each test, as they run in parallel
+    fn workspace(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cargo-binutils-watch-{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}").unwrap();
+        dir
+    }
+
+    #[test]
+    fn detects_added_and_removed_files() {
+        let root = workspace("added");
+        let target_dir = root.join("target");
+        let before = Snapshot::take(&root, &target_dir);
+        assert_eq!(before.files, 1);
+
+        fs::write(root.join("src/lib.rs"), "").unwrap();
+        let added = Snapshot::take(&root, &target_dir);
+        assert_ne!(added, before);
+
+        fs::remove_file(root.join("src/lib.rs")).unwrap();
+        assert_eq!(Snapshot::take(&root, &target_dir).files, before.files);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn detects_modified_files() {
+        let root = workspace("modified");
+        let target_dir = root.join("target");
+        let before = Snapshot::take(&root, &target_dir);
+
+        // Past the resolution of the modification times of most file systems
+        thread::sleep(Duration::from_millis(50));
+        fs::write(root.join("src/main.rs"), "fn main() { loop {} }").unwrap();
+        assert_ne!(Snapshot::take(&root, &target_dir), before);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn ignores_the_target_directory_and_git() {
+        let root = workspace("ignored");
+        let target_dir = root.join("target");
+        fs::create_dir_all(target_dir.join("debug")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        let before = Snapshot::take(&root, &target_dir);
+
+        fs::write(target_dir.join("debug/app"), "").unwrap();
+        fs::write(root.join(".git/index"), "").unwrap();
+        assert_eq!(Snapshot::take(&root, &target_dir), before);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn detects_changes_to_the_cargo_config() {
+        let root = workspace("cargo-config");
+        let target_dir = root.join("target");
+        fs::create_dir_all(root.join(".cargo")).unwrap();
+        fs::write(root.join(".cargo/config.toml"), "").unwrap();
+        let before = Snapshot::take(&root, &target_dir);
+
+        thread::sleep(Duration::from_millis(50));
+        fs::write(
+            root.join(".cargo/config.toml"),
+            "[build]\ntarget = \"thumbv7m-none-eabi\"\n",
+        )
+        .unwrap();
+        assert_ne!(Snapshot::take(&root, &target_dir), before);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
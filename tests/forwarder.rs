@@ -0,0 +1,127 @@
+//! Tests of the `rust-$tool` forwarders, run as child processes so that their environment can be
+//! changed freely
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::{env, fs};
+
+// A fresh, empty, scratch directory for the test `name`
+fn scratch(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("cargo-binutils-forwarder-{name}"));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Writes an executable shell script
+#[cfg(unix)]
+fn script(path: &Path, body: &str) {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::write(path, format!("#!/bin/sh\n{body}\n")).unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+// A fake toolchain in `dir` whose sysroot is `dir/sysroot`, returns the path of its `rustc` and
+// the directory where the tools are looked up
+#[cfg(unix)]
+fn toolchain(dir: &Path) -> (PathBuf, PathBuf) {
+    let host = rustc_version::version_meta().unwrap().host;
+    let sysroot = dir.join("sysroot");
+    let bin = sysroot.join("lib").join("rustlib").join(host).join("bin");
+    fs::create_dir_all(&bin).unwrap();
+
+    // Anything but the sysroot query is answered by the real rustc
+    let rustc = dir.join("rustc");
+    script(
+        &rustc,
+        &format!(
+            "if [ \"$1\" = --print ] && [ \"$2\" = sysroot ]; then echo '{}'; else exec rustc \"$@\"; fi",
+            sysroot.display()
+        ),
+    );
+    (rustc, bin)
+}
+
+fn rust_nm(rustc: &Path, path: Option<&Path>) -> Output {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_rust-nm"));
+    cmd.env("RUSTC", rustc);
+    if let Some(dir) = path {
+        let paths = env::var_os("PATH").unwrap_or_default();
+        let paths = env::join_paths([dir.to_owned()].into_iter().chain(env::split_paths(&paths)));
+        cmd.env("PATH", paths.unwrap());
+    }
+    cmd.output().unwrap()
+}
+
+#[test]
+fn exits_with_101_if_rustc_fails() {
+    let dir = scratch("no-rustc");
+
+    let output = rust_nm(&dir.join("rustc"), None);
+
+    assert_eq!(output.status.code(), Some(101));
+}
+
+#[cfg(unix)]
+#[test]
+fn exits_with_102_if_the_tool_is_missing() {
+    let dir = scratch("missing-tool");
+    let (rustc, _) = toolchain(&dir);
+
+    let output = rust_nm(&rustc, None);
+
+    assert_eq!(output.status.code(), Some(102));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("Could not find tool: nm"), "{stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_the_tool_of_the_toolchain_over_the_one_in_path() {
+    let dir = scratch("conflicting-path");
+    let (rustc, bin) = toolchain(&dir);
+    script(&bin.join("llvm-nm"), "echo toolchain");
+    // e.g. left over from an older, incompatible, installation
+    let stale = dir.join("stale");
+    fs::create_dir(&stale).unwrap();
+    script(&stale.join("llvm-nm"), "echo stale");
+    script(&stale.join("rust-nm"), "echo stale");
+
+    let output = rust_nm(&rustc, Some(&stale));
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "toolchain\n");
+}
+
+#[test]
+fn forwards_version() {
+    let output = Command::new(env!("CARGO_BIN_EXE_rust-objdump"))
+        .arg("--version")
+        .output()
+        .unwrap();
+
+    if cargo_binutils::Tool::Objdump.resolve().is_ok() {
+        assert_eq!(output.status.code(), Some(0));
+        assert!(!output.stdout.is_empty());
+    } else {
+        assert_eq!(output.status.code(), Some(102));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn forwards_the_arguments() {
+    let dir = scratch("arguments");
+    let (rustc, bin) = toolchain(&dir);
+    script(&bin.join("llvm-objdump"), "echo \"$@\"");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rust-objdump"))
+        .env("RUSTC", rustc)
+        .args(["--version", "-d"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "--version -d\n");
+}
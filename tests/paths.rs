@@ -0,0 +1,49 @@
+//! Tests of the public API that looks the tools up, against a fake sysroot. They change the
+//! environment of the process so they live in this test binary of their own.
+#![cfg(unix)]
+
+use std::os::unix::fs::PermissionsExt;
+use std::{env, fs};
+
+use cargo_binutils::{paths, Tool};
+
+// The only test of this binary, so no other test sees `RUSTC`
+#[test]
+fn resolves_the_tools_of_the_sysroot() {
+    let dir = env::temp_dir().join("cargo-binutils-paths");
+    let _ = fs::remove_dir_all(&dir);
+    let host = rustc_version::version_meta().unwrap().host;
+    let sysroot = dir.join("sysroot");
+    let bin = sysroot.join("lib/rustlib").join(host).join("bin");
+    fs::create_dir_all(&bin).unwrap();
+
+    // Anything but the sysroot query is answered by the real rustc
+    let rustc = dir.join("rustc");
+    fs::write(
+        &rustc,
+        format!(
+            "#!/bin/sh\nif [ \"$1\" = --print ] && [ \"$2\" = sysroot ]; then echo '{}'; else exec rustc \"$@\"; fi\n",
+            sysroot.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).unwrap();
+    env::set_var("RUSTC", &rustc);
+
+    assert_eq!(paths::sysroot().unwrap(), sysroot.to_str().unwrap());
+    assert_eq!(paths::rustlib().unwrap(), bin);
+
+    let e = Tool::Nm.resolve().unwrap_err().to_string();
+    assert!(e.starts_with("Could not find tool: nm\n"), "{e}");
+    assert!(
+        e.contains(&bin.join("llvm-nm").display().to_string()),
+        "{e}"
+    );
+
+    fs::write(bin.join("llvm-nm"), "").unwrap();
+    assert_eq!(Tool::Nm.resolve().unwrap(), bin.join("llvm-nm"));
+    assert_eq!(Tool::Objdump.path().unwrap(), bin.join("llvm-objdump"));
+
+    env::remove_var("RUSTC");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,349 @@
+use std::{env, fs};
+
+use cargo_binutils::Tool;
+
+// Runs `cargo $tool $args` and returns its exit code and output
+fn run(tool: Tool, args: &[&str]) -> (i32, String) {
+    let argv = [format!("cargo-{tool}"), tool.name().to_owned()];
+    let matches = cargo_binutils::command(tool, None)
+        .get_matches_from(argv.iter().map(String::as_str).chain(args.iter().copied()));
+
+    let mut out = vec![];
+    let code = cargo_binutils::run_with_output(tool, matches, &mut out).unwrap();
+    (code, String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn dry_run_prints_the_command() {
+    let (code, out) = run(Tool::Cxxfilt, &["--dry-run", "--", "_ZN3foo3barE"]);
+
+    assert_eq!(code, 0);
+    let exe = Tool::Cxxfilt.exe();
+    assert!(out.contains(&exe), "{out}");
+    assert!(out.trim_end().ends_with("\"_ZN3foo3barE\""), "{out}");
+}
+
+#[test]
+fn dry_run_doesnt_build_without_the_convenience_flags() {
+    let (code, out) = run(Tool::Cov, &["--dry-run", "--", "report", "--help"]);
+
+    assert_eq!(code, 0);
+    assert_eq!(out.lines().count(), 1, "{out}");
+    assert!(out.contains(&Tool::Cov.exe()), "{out}");
+    assert!(out.trim_end().ends_with("\"report\" \"--help\""), "{out}");
+}
+
+#[test]
+fn reports_a_failed_build() {
+    let dir = env::temp_dir().join("cargo-binutils-run-broken");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(
+        dir.join("Cargo.toml"),
+        "[package]\nname = \"broken\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+    )
+    .unwrap();
+    fs::write(dir.join("src/main.rs"), "fn main() {").unwrap();
+
+    let manifest = dir.join("Cargo.toml");
+    let matches = cargo_binutils::command(Tool::Size, None).get_matches_from([
+        "cargo-size",
+        "size",
+        "--quiet",
+        "--manifest-path",
+        manifest.to_str().unwrap(),
+    ]);
+    let e = cargo_binutils::run_with_output(Tool::Size, matches, &mut vec![]).unwrap_err();
+
+    let e = e.to_string();
+    assert!(e.starts_with("`cargo build` failed"), "{e}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn disassembles_a_staticlib() {
+    let dir = env::temp_dir().join("cargo-binutils-run-staticlib");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(
+        dir.join("Cargo.toml"),
+        "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+         [lib]\ncrate-type = [\"staticlib\"]\n\n[workspace]\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("src/lib.rs"),
+        "#[no_mangle]\npub extern \"C\" fn f() {}\n",
+    )
+    .unwrap();
+
+    let manifest = dir.join("Cargo.toml");
+    let (code, out) = run(
+        Tool::Objdump,
+        &[
+            "--dry-run",
+            "--lib",
+            "--manifest-path",
+            manifest.to_str().unwrap(),
+            "--",
+            "-d",
+        ],
+    );
+
+    assert_eq!(code, 0);
+    // The archive itself, objdump goes through its members
+    assert!(out.contains("/target/debug\""), "{out}");
+    assert!(out.contains(" \"libapp.a\" "), "{out}");
+    assert!(out.contains(" \"--triple\" "), "{out}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn prints_the_artifact() {
+    let dir = env::temp_dir().join("cargo-binutils-run-print-artifact");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("src/bin")).unwrap();
+    fs::write(
+        dir.join("Cargo.toml"),
+        "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+    )
+    .unwrap();
+    fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+    fs::write(dir.join("src/bin/other.rs"), "fn main() {}\n").unwrap();
+
+    let manifest = dir.join("Cargo.toml");
+    let (code, out) = run(
+        Tool::Size,
+        &[
+            "--print-artifact",
+            "--bin",
+            "other",
+            "--release",
+            "--manifest-path",
+            manifest.to_str().unwrap(),
+        ],
+    );
+
+    assert_eq!(code, 0);
+    let other = dir
+        .join("target/release/other")
+        .with_extension(env::consts::EXE_EXTENSION);
+    assert_eq!(out, format!("{}\n", other.display()));
+    assert!(other.is_file());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn inspects_the_workspace_of_the_manifest_path() {
+    let dir = env::temp_dir().join("cargo-binutils-run-sibling");
+    let _ = fs::remove_dir_all(&dir);
+    for member in ["a", "b"] {
+        fs::create_dir_all(dir.join(member).join("src")).unwrap();
+        fs::write(
+            dir.join(member).join("Cargo.toml"),
+            format!("[package]\nname = \"{member}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n"),
+        )
+        .unwrap();
+        fs::write(dir.join(member).join("src/main.rs"), "fn main() {}\n").unwrap();
+    }
+    fs::write(
+        dir.join("Cargo.toml"),
+        "[workspace]\nmembers = [\"a\", \"b\"]\nresolver = \"2\"\n",
+    )
+    .unwrap();
+
+    // Not the workspace of the current directory
+    let manifest = dir.join("Cargo.toml");
+    let manifest = manifest.to_str().unwrap();
+    let matches = cargo_binutils::command(Tool::Size, None).get_matches_from([
+        "cargo-size",
+        "size",
+        "--manifest-path",
+        manifest,
+    ]);
+    let e = cargo_binutils::run_with_output(Tool::Size, matches, &mut vec![]).unwrap_err();
+    assert!(e.to_string().ends_with("Workspace members: a, b"), "{e}");
+
+    let (code, out) = run(
+        Tool::Size,
+        &[
+            "--print-artifact",
+            "--package",
+            "b",
+            "--manifest-path",
+            manifest,
+        ],
+    );
+    assert_eq!(code, 0);
+    let b = dir
+        .join("target/debug/b")
+        .with_extension(env::consts::EXE_EXTENSION);
+    assert_eq!(out, format!("{}\n", b.display()));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn suggests_the_matching_artifacts() {
+    let dir = env::temp_dir().join("cargo-binutils-run-two-bins");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("src/bin")).unwrap();
+    let cargo_toml =
+        "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n";
+    fs::write(dir.join("Cargo.toml"), cargo_toml).unwrap();
+    fs::write(dir.join("src/bin/one.rs"), "fn main() {}\n").unwrap();
+    fs::write(dir.join("src/bin/two.rs"), "fn main() {}\n").unwrap();
+
+    let manifest = dir.join("Cargo.toml");
+    let manifest = manifest.to_str().unwrap();
+    let matches = cargo_binutils::command(Tool::Size, None).get_matches_from([
+        "cargo-size",
+        "size",
+        "--manifest-path",
+        manifest,
+    ]);
+    let e = cargo_binutils::run_with_output(Tool::Size, matches, &mut vec![]).unwrap_err();
+    let e = e.to_string();
+    let suggestions = e.lines().skip(1).map(str::trim).collect::<Vec<_>>();
+    assert_eq!(suggestions.len(), 2, "{e}");
+    assert!(suggestions[0].starts_with("--bin one ("), "{e}");
+    assert!(suggestions[1].starts_with("--bin two ("), "{e}");
+
+    // Like `cargo run`
+    let cargo_toml = cargo_toml.replace("edition", "default-run = \"two\"\nedition");
+    fs::write(dir.join("Cargo.toml"), cargo_toml).unwrap();
+    let (code, out) = run(
+        Tool::Size,
+        &["--print-artifact", "--manifest-path", manifest],
+    );
+    assert_eq!(code, 0);
+    let two = dir
+        .join("target/debug/two")
+        .with_extension(env::consts::EXE_EXTENSION);
+    assert_eq!(out, format!("{}\n", two.display()));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn runs_the_tool_on_each_artifact() {
+    let dir = env::temp_dir().join("cargo-binutils-run-workspace");
+    let _ = fs::remove_dir_all(&dir);
+    for member in ["a", "b"] {
+        fs::create_dir_all(dir.join(member).join("src")).unwrap();
+        fs::write(
+            dir.join(member).join("Cargo.toml"),
+            format!("[package]\nname = \"{member}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n"),
+        )
+        .unwrap();
+        fs::write(dir.join(member).join("src/main.rs"), "fn main() {}\n").unwrap();
+    }
+    fs::create_dir_all(dir.join("a/examples")).unwrap();
+    fs::write(dir.join("a/examples/ex.rs"), "fn main() {}\n").unwrap();
+    fs::write(
+        dir.join("Cargo.toml"),
+        "[workspace]\nmembers = [\"a\", \"b\"]\nresolver = \"2\"\n",
+    )
+    .unwrap();
+    let manifest = dir.join("Cargo.toml");
+    let manifest = manifest.to_str().unwrap();
+    // The artifact headers, each followed by the command of the tool
+    let headers = |out: &str| {
+        let lines = out.lines().collect::<Vec<_>>();
+        for pair in lines.split(|line| line.is_empty()) {
+            assert_eq!(pair.len(), 2, "{out}");
+        }
+        lines
+            .iter()
+            .filter_map(|line| line.strip_suffix(':'))
+            .map(|path| path.strip_prefix(dir.to_str().unwrap()).unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+    let exe = |path: &str| format!("{path}{}", env::consts::EXE_SUFFIX);
+
+    let (code, out) = run(
+        Tool::Size,
+        &["--dry-run", "--workspace", "--manifest-path", manifest],
+    );
+    assert_eq!(code, 0);
+    assert_eq!(
+        headers(&out),
+        [exe("/target/debug/a"), exe("/target/debug/b")]
+    );
+
+    let (code, out) = run(
+        Tool::Nm,
+        &[
+            "--dry-run",
+            "--all-targets",
+            "-p",
+            "a",
+            "--manifest-path",
+            manifest,
+        ],
+    );
+    assert_eq!(code, 0);
+    let headers = headers(&out);
+    assert!(headers.contains(&exe("/target/debug/a")), "{out}");
+    assert!(headers.contains(&exe("/target/debug/examples/ex")), "{out}");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn builds_in_the_target_dir() {
+    let dir = env::temp_dir().join("cargo-binutils-run-target-dir");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("app/src")).unwrap();
+    fs::write(
+        dir.join("app/Cargo.toml"),
+        "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+    )
+    .unwrap();
+    fs::write(dir.join("app/src/main.rs"), "fn main() {}\n").unwrap();
+
+    let manifest = dir.join("app/Cargo.toml");
+    let target_dir = dir.join("out");
+    let (code, out) = run(
+        Tool::Size,
+        &[
+            "--print-artifact",
+            "--manifest-path",
+            manifest.to_str().unwrap(),
+            "--target-dir",
+            target_dir.to_str().unwrap(),
+        ],
+    );
+
+    assert_eq!(code, 0);
+    let app = target_dir
+        .join("debug/app")
+        .with_extension(env::consts::EXE_EXTENSION);
+    assert_eq!(out, format!("{}\n", app.display()));
+    assert!(app.is_file());
+    assert!(!dir.join("app/target").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn writes_the_output_to_a_file() {
+    let path = env::temp_dir().join("cargo-binutils-run-output.txt");
+    // Truncated rather than appended to
+    fs::write(&path, "x".repeat(4096)).unwrap();
+
+    let matches = cargo_binutils::command(Tool::Cxxfilt, None).get_matches_from([
+        "cargo-cxxfilt",
+        "cxxfilt",
+        "--dry-run",
+        "--output",
+        path.to_str().unwrap(),
+        "--",
+        "_ZN3foo3barE",
+    ]);
+    assert_eq!(cargo_binutils::run(Tool::Cxxfilt, matches).unwrap(), 0);
+
+    let out = fs::read_to_string(&path).unwrap();
+    assert_eq!(out.lines().count(), 1, "{out}");
+    assert!(out.trim_end().ends_with("\"_ZN3foo3barE\""), "{out}");
+    fs::remove_file(&path).unwrap();
+}